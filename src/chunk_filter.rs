@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use mca::RegionReader;
+use simdnbt::borrow::NbtCompound;
+
+const CHUNK_PER_REGION_SIDE: usize = 32;
+
+/// The `InhabitedTime` of a chunk: ticks spent in it by players, summed over all players.
+/// Stored at the root of the chunk since 1.18, and in its `Level` compound before.
+pub fn inhabited_time(chunk: &NbtCompound) -> Option<u64> {
+    chunk
+        .long("InhabitedTime")
+        .or_else(|| chunk.compound("Level")?.long("InhabitedTime"))
+        .map(|ticks| ticks.max(0) as u64)
+}
+
+/// The block region file holding the chunks of an entities region file, which stores no
+/// `InhabitedTime` of its own.
+pub fn block_region_path(entities_path: &Path) -> Option<PathBuf> {
+    let dimension_root = entities_path.parent()?.parent()?;
+    Some(
+        dimension_root
+            .join("region")
+            .join(entities_path.file_name()?),
+    )
+}
+
+/// Reads the `InhabitedTime` of every chunk of a region file, by chunk index (`x + z * 32`).
+/// Chunks that cannot be read are left out.
+pub fn load_inhabited_times(region_path: &Path, verbose: bool) -> HashMap<usize, u64> {
+    let mut times = HashMap::new();
+    let data = match std::fs::read(region_path) {
+        Ok(data) => data,
+        Err(e) => {
+            if verbose {
+                eprintln!(
+                    "Failed to read {} for chunk inhabited times: {e}",
+                    region_path.display()
+                );
+            }
+            return times;
+        }
+    };
+    let Ok(region_reader) = RegionReader::new(&data) else {
+        return times;
+    };
+    for cz in 0..CHUNK_PER_REGION_SIDE {
+        for cx in 0..CHUNK_PER_REGION_SIDE {
+            let Ok(Some(chunk)) = region_reader.get_chunk(cx, cz) else {
+                continue;
+            };
+            let Ok(decompressed) = chunk.decompress() else {
+                continue;
+            };
+            if let Some(ticks) = root_inhabited_time(&decompressed) {
+                times.insert(cx + cz * CHUNK_PER_REGION_SIDE, ticks);
+            }
+        }
+    }
+    times
+}
+
+const TAG_END: u8 = 0;
+const TAG_LONG: u8 = 4;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+/// Deepest nesting of lists and compounds read, as in `simdnbt`, so that corrupt chunks fail
+/// instead of overflowing the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Same as `inhabited_time` on uncompressed chunk NBT, skipping over the other tags instead
+/// of reading the whole chunk.
+pub fn root_inhabited_time(data: &[u8]) -> Option<u64> {
+    let mut cursor = Cursor::new(data);
+    if read_u8(&mut cursor)? != TAG_COMPOUND {
+        return None;
+    }
+    skip_string(&mut cursor)?;
+    find_inhabited_time(&mut cursor, 1)
+}
+
+/// Looks for `InhabitedTime` in the compound at `cursor`, `depth` levels deep, and in its
+/// `Level` compound if it is the root.
+fn find_inhabited_time(cursor: &mut Cursor<&[u8]>, depth: usize) -> Option<u64> {
+    loop {
+        let tag = read_u8(cursor)?;
+        if tag == TAG_END {
+            return None;
+        }
+        let name = read_string(cursor)?;
+        match (tag, name) {
+            (TAG_LONG, b"InhabitedTime") => {
+                return Some(i64::from_be_bytes(read_array(cursor)?).max(0) as u64);
+            }
+            (TAG_COMPOUND, b"Level") if depth == 1 => {
+                return find_inhabited_time(cursor, depth + 1);
+            }
+            _ => skip_payload(cursor, tag, depth + 1)?,
+        }
+    }
+}
+
+fn read_array<const N: usize>(cursor: &mut Cursor<&[u8]>) -> Option<[u8; N]> {
+    let start = cursor.position() as usize;
+    let bytes = cursor.get_ref().get(start..start + N)?.try_into().ok()?;
+    cursor.set_position((start + N) as u64);
+    Some(bytes)
+}
+
+fn read_u8(cursor: &mut Cursor<&[u8]>) -> Option<u8> {
+    read_array::<1>(cursor).map(|[byte]| byte)
+}
+
+fn read_string<'a>(cursor: &mut Cursor<&'a [u8]>) -> Option<&'a [u8]> {
+    let length = u16::from_be_bytes(read_array(cursor)?) as usize;
+    let start = cursor.position() as usize;
+    let data: &'a [u8] = cursor.get_ref();
+    let bytes = data.get(start..start + length)?;
+    cursor.set_position((start + length) as u64);
+    Some(bytes)
+}
+
+fn skip_string(cursor: &mut Cursor<&[u8]>) -> Option<()> {
+    read_string(cursor).map(|_| ())
+}
+
+fn skip(cursor: &mut Cursor<&[u8]>, length: usize) -> Option<()> {
+    let end = (cursor.position() as usize).checked_add(length)?;
+    (end <= cursor.get_ref().len()).then(|| cursor.set_position(end as u64))
+}
+
+/// Moves `cursor` past the payload of a tag of type `tag`, `depth` levels deep.
+fn skip_payload(cursor: &mut Cursor<&[u8]>, tag: u8, depth: usize) -> Option<()> {
+    if depth > MAX_DEPTH && matches!(tag, TAG_LIST | TAG_COMPOUND) {
+        return None;
+    }
+    let length =
+        |cursor: &mut Cursor<&[u8]>| usize::try_from(i32::from_be_bytes(read_array(cursor)?)).ok();
+    match tag {
+        1 => skip(cursor, 1),
+        2 => skip(cursor, 2),
+        3 | 5 => skip(cursor, 4),
+        4 | 6 => skip(cursor, 8),
+        7 => {
+            let count = length(cursor)?;
+            skip(cursor, count)
+        }
+        8 => skip_string(cursor),
+        TAG_LIST => {
+            let element = read_u8(cursor)?;
+            for _ in 0..length(cursor)? {
+                skip_payload(cursor, element, depth + 1)?;
+            }
+            Some(())
+        }
+        TAG_COMPOUND => loop {
+            let tag = read_u8(cursor)?;
+            if tag == TAG_END {
+                return Some(());
+            }
+            skip_string(cursor)?;
+            skip_payload(cursor, tag, depth + 1)?;
+        },
+        11 => {
+            let count = length(cursor)?;
+            skip(cursor, count.checked_mul(4)?)
+        }
+        12 => {
+            let count = length(cursor)?;
+            skip(cursor, count.checked_mul(8)?)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_block_region_of_entities() {
+        assert_eq!(
+            block_region_path(Path::new("world/DIM-1/entities/r.0.-1.mca")),
+            Some(Path::new("world/DIM-1/region/r.0.-1.mca").to_path_buf())
+        );
+    }
+
+    #[test]
+    fn reads_only_the_root_inhabited_time() {
+        let chunk = crate::nbt_utils::snbt_to_binary(
+            r#"{Biomes:[I;1,2],BlockEntities:[{id:"minecraft:chest",InhabitedTime:5L}],Carving:[B;1b],Heightmaps:{WORLD_SURFACE:[L;1L,2L]},Height:1.5d,InhabitedTime:1200L,Status:"full"}"#,
+        );
+        assert_eq!(root_inhabited_time(&chunk), Some(1200));
+
+        let legacy = crate::nbt_utils::snbt_to_binary(
+            r#"{DataVersion:1343,Level:{Entities:[],InhabitedTime:-3L}}"#,
+        );
+        assert_eq!(root_inhabited_time(&legacy), Some(0));
+
+        let missing = crate::nbt_utils::snbt_to_binary(r#"{Level:{Sections:[B;1b,2b]}}"#);
+        assert_eq!(root_inhabited_time(&missing), None);
+        assert_eq!(root_inhabited_time(&chunk[..chunk.len() / 2]), None);
+    }
+
+    #[test]
+    fn gives_up_on_deeply_nested_lists() {
+        // {a:[[...[]...]],InhabitedTime:7L}, with `levels` lists of one list each
+        let nested = |levels: usize| {
+            let mut data = vec![TAG_COMPOUND, 0, 0, TAG_LIST, 0, 1, b'a'];
+            for _ in 1..levels {
+                data.extend([TAG_LIST, 0, 0, 0, 1]);
+            }
+            data.extend([TAG_END, 0, 0, 0, 0]);
+            data.extend([TAG_LONG, 0, 13]);
+            data.extend(b"InhabitedTime");
+            data.extend(7i64.to_be_bytes());
+            data.push(TAG_END);
+            data
+        };
+        assert_eq!(root_inhabited_time(&nested(100)), Some(7));
+        assert_eq!(root_inhabited_time(&nested(1_000_000)), None);
+    }
+}
@@ -1,34 +1,157 @@
-use std::path::PathBuf;
+use std::{collections::HashSet, path::PathBuf};
 
-use clap::{ArgGroup, Parser, ValueEnum};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use valence_nbt::Value;
 
+use crate::civil_date;
+
 /// Count items in a Minecraft world, with optional per-item NBT filters and coordinates
 #[derive(Parser, Debug)]
-#[command(version, group(ArgGroup::new("mode").args(["all", "items"]).required(true)))]
+#[command(version, subcommand_negates_reqs = true, group(ArgGroup::new("mode").args(["all", "items", "filters"]).multiple(true).required(true)))]
 pub struct CliArgs {
-    #[arg(short, long, value_name = "PATH")]
-    pub world_path: PathBuf,
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        required_unless_present = "from_snapshot"
+    )]
+    pub world_path: Option<PathBuf>,
 
     /// Count all items
-    #[arg(long, group = "mode")]
+    #[arg(global = true, long, group = "mode", conflicts_with_all = ["items", "filters"])]
     pub all: bool,
 
     /// Specify items to count
     #[arg(
+        global = true,
         short,
         long = "item",
         value_name = "ITEM",
         group = "mode",
         num_args = 1..,
-        long_help = "Specify items to count, each in the form: ITEM_ID{nbt}\n\nExamples:\n\n--item minecraft:diamond\n--item minecraft:shulker_box{components:{\"minecraft:item_name\":\"Portable Chest\"}}"
+        long_help = "Specify items to count, each in the form: ITEM_ID{nbt} or #TAG_ID{nbt}\n\nExamples:\n\n--item minecraft:diamond\n--item '#minecraft:logs'\n--item minecraft:shulker_box{components:{\"minecraft:item_name\":\"Portable Chest\"}}"
     )]
     pub items: Vec<String>,
 
+    /// Boolean filter expression combining item specs with `and`, `or`, `not` and parentheses
+    #[arg(
+        global = true,
+        long = "filter",
+        value_name = "EXPR",
+        group = "mode",
+        long_help = "Boolean filter expression combining item specs (ITEM_ID{nbt}, #TAG_ID{nbt} or {nbt}) with `and`, `or`, `not` (or `&&`, `||`, `!`) and parentheses.\nMultiple --filter expressions must all match, and are combined with --item.\n\nExample:\n\n--filter 'enchanted_book and not {components:{\"minecraft:stored_enchantments\":{\"minecraft:mending\":1}}}'"
+    )]
+    pub filters: Vec<String>,
+
+    /// Exclude items matching any of these item specs
+    #[arg(
+        global = true,
+        long = "exclude",
+        value_name = "ITEM",
+        num_args = 1..,
+        long_help = "Exclude items matching any of these item specs, in the same form as --item.\n\nExample:\n\n--all --exclude minecraft:dirt minecraft:cobblestone"
+    )]
+    pub excludes: Vec<String>,
+
+    /// Only match items whose custom name reads exactly TEXT (case-insensitive, formatting ignored)
+    #[arg(global = true, long, value_name = "TEXT", group = "mode")]
+    pub name: Option<String>,
+
+    /// Only match items with a lore line containing TEXT (case-insensitive, formatting ignored)
+    #[arg(global = true, long, value_name = "TEXT", group = "mode")]
+    pub lore_contains: Vec<String>,
+
+    /// Only match items with an enchantment, e.g. `sharpness>=5` or `book:mending`
+    #[arg(
+        global = true,
+        long = "enchantment",
+        value_name = "SPEC",
+        group = "mode",
+        long_help = "Only match items carrying an enchantment, given as [item:|book:]ENCHANTMENT_ID[LEVEL].\n`item:` only looks at enchantments applied to the item, `book:` only at enchantments stored in books.\nLEVEL is one of =N, >=N, <=N, >N, <N or =MIN..MAX. Can be repeated; every enchantment must be present.\n\nExamples:\n\n--enchantment 'sharpness>=5'\n--enchantment book:mending"
+    )]
+    pub enchantments: Vec<String>,
+
+    /// Only match items nested at least N levels deep inside container items (0 = loose items)
+    #[arg(global = true, long, value_name = "N")]
+    pub min_depth: Option<usize>,
+
+    /// Only match items nested at most N levels deep inside container items (0 = loose items only)
+    #[arg(global = true, long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Only match items nested (at any depth) inside one of these container items, e.g. `shulker_box` or `#minecraft:bundles`
+    #[arg(global = true, long, value_name = "ITEM", num_args = 1..)]
+    pub inside: Vec<String>,
+
+    /// Only count items held by block entities, entities or players matching PATTERN[{nbt}]
+    #[arg(
+        global = true,
+        long = "in-container",
+        value_name = "PATTERN",
+        long_help = "Only count items held by block entities, entities or players (`minecraft:player`) whose ID matches PATTERN, where `*` matches any characters.\nAn optional SNBT predicate must be a subset of the holder's own NBT. Can be repeated; a holder must match at least one.\n\nExamples:\n\n--in-container hopper\n--in-container '*shulker_box'\n--in-container 'chest{Lock:{}}'"
+    )]
+    pub in_containers: Vec<String>,
+
+    /// Also look for nested items under this NBT path, relative to each item
+    #[arg(
+        global = true,
+        long = "nested-item-path",
+        value_name = "PATH",
+        long_help = "Also look for items nested under this NBT path, relative to each item, in the same syntax as --filter paths.\nUse [] for lists of items and * for compounds keyed by slot. Vanilla paths (containers, bundles, crossbow projectiles, block entity and entity data, ...) are searched by default.\n\nExample:\n\n--nested-item-path 'components.\"minecraft:custom_data\".backpack[]'"
+    )]
+    pub nested_item_paths: Vec<String>,
+
+    /// Do not search the vanilla nested item paths, only those given with --nested-item-path
+    #[arg(global = true, long)]
+    pub no_default_nested_paths: bool,
+
+    /// Only scan region file chunks last saved at or after DATE
+    #[arg(
+        global = true,
+        long,
+        value_name = "DATE",
+        value_parser = parse_date,
+        long_help = "Only scan region file chunks last saved at or after DATE, read from the region file header without decompressing the chunk.\nDATE is YYYY-MM-DD, YYYY-MM-DDTHH:MM[:SS] (UTC) or seconds since the Unix epoch. Player data is not filtered.\n\nExample:\n\n--modified-since 2024-06-01"
+    )]
+    pub modified_since: Option<u64>,
+
+    /// Only scan region file chunks last saved before DATE
+    #[arg(global = true, long, value_name = "DATE", value_parser = parse_date)]
+    pub modified_before: Option<u64>,
+
+    /// Only scan chunks players have spent at least TICKS in, summed over all players (20 ticks per second)
+    #[arg(
+        global = true,
+        long,
+        value_name = "TICKS",
+        long_help = "Only scan chunks players have spent at least TICKS game ticks in, summed over all players (20 ticks per second), from the InhabitedTime of the chunk.\nEntities are filtered by the chunk at the same position in the region folder. Player data is not filtered.\n\nExample:\n\n--min-inhabited-ticks 72000"
+    )]
+    pub min_inhabited_ticks: Option<u64>,
+
+    /// Compare numeric NBT values by value across integer types (1b = 1s = 1 = 1L) and float/double
+    #[arg(global = true, long)]
+    pub lenient_numbers: bool,
+
+    /// Path to a vanilla data directory or client .jar used to resolve item tags
+    #[arg(global = true, long, value_name = "PATH")]
+    pub vanilla_data: Option<PathBuf>,
+
+    /// List villager and wandering trader offers instead of item counts
+    #[arg(
+        long,
+        group = "mode",
+        long_help = "List villager and wandering trader offers (buy, second buy, sell, uses and max uses) instead of item counts, grouped by profession, level and sold item.\nWith --item, --filter and the other item filters, only offers where the sold or bought items match are listed; --in-container restricts the traders.\n\nExample:\n\n--trades --enchantment book:mending"
+    )]
+    pub trades: bool,
+
     /// Which summary format to display.
-    #[arg(short, long, value_enum, default_value_t = ViewMode::ById)]
+    #[arg(global = true, short, long, value_enum, default_value_t = ViewMode::ById)]
     pub view: ViewMode,
 
+    /// Add a nesting depth column to the detailed and by-id views
+    #[arg(long)]
+    pub group_by_depth: bool,
+
     /// Show full NBT data in item summaries
     #[arg(long)]
     pub show_nbt: bool,
@@ -46,12 +169,108 @@ pub struct CliArgs {
     pub per_data_type_summary: bool,
 
     /// Increase output verbosity
-    #[arg(long)]
+    #[arg(global = true, long)]
     pub verbose: bool,
 
     /// Specify the output format
-    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    #[arg(global = true, short, long, value_enum, default_value_t = OutputFormat::Table)]
     pub format: OutputFormat,
+
+    /// Also write every matched item and its holder to a new SQLite database
+    #[arg(long, value_name = "FILE")]
+    pub export_sqlite: Option<PathBuf>,
+
+    /// Also write a PNG heatmap of matched items per dimension to this directory
+    #[arg(long, value_name = "DIR")]
+    pub heatmap: Option<PathBuf>,
+
+    /// Size of a heatmap cell
+    #[arg(long, value_enum, default_value_t = HeatmapResolution::Chunk, requires = "heatmap")]
+    pub heatmap_resolution: HeatmapResolution,
+
+    /// Also save the item counts to a snapshot file, gzip-compressed if FILE ends in `.gz`
+    #[arg(long, value_name = "FILE", conflicts_with = "trades")]
+    pub save_snapshot: Option<PathBuf>,
+
+    /// Also save the holder and position of every matched item in the snapshot
+    #[arg(long, requires = "save_snapshot")]
+    pub snapshot_locations: bool,
+
+    /// Show the results saved in a snapshot instead of scanning a world
+    #[arg(
+        long,
+        value_name = "FILE",
+        group = "mode",
+        conflicts_with_all = [
+            "world_path", "all", "items", "filters", "excludes", "name", "lore_contains",
+            "enchantments", "min_depth", "max_depth", "inside", "in_containers",
+            "nested_item_paths", "modified_since", "modified_before", "min_inhabited_ticks",
+            "trades", "per_source_summary", "export_sqlite", "heatmap",
+            "save_snapshot",
+        ],
+        long_help = "Show the results saved in a snapshot with --save-snapshot instead of scanning a world, with any --view, --format and summary options.\nItem filters cannot be applied to a snapshot; the ndjson format needs a snapshot saved with --snapshot-locations.\n\nExample:\n\n--from-snapshot scan.json.gz --view by-enchantment --format csv"
+    )]
+    pub from_snapshot: Option<PathBuf>,
+
+    /// Cache the results of each region file chunk in this directory, to rescan only changed chunks
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["trades", "per_source_summary", "from_snapshot"],
+        long_help = "Cache the results of each region file chunk in this directory (created if missing), and reuse them in later runs for chunks the game did not save again since.\nUnchanged region files are not read at all. Results cached with other item filters or options are ignored.\n\nExample:\n\n--cache-dir ~/.cache/nbt-sniffer"
+    )]
+    pub cache_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl CliArgs {
+    /// Whether the scan must record each matched item, not only count it.
+    pub fn collects_occurrences(&self) -> bool {
+        self.streams_ndjson()
+            || self.export_sqlite.is_some()
+            || self.snapshot_locations
+            || self.diff_args().is_some_and(|diff| diff.by_location)
+    }
+
+    pub fn diff_args(&self) -> Option<&DiffArgs> {
+        match &self.command {
+            Some(Command::Diff(diff)) => Some(diff),
+            _ => None,
+        }
+    }
+
+    pub fn history_args(&self) -> Option<&HistoryArgs> {
+        match &self.command {
+            Some(Command::History(history)) => Some(history),
+            _ => None,
+        }
+    }
+
+    /// Whether a chunk last saved at `timestamp`, in seconds since the Unix epoch, passes
+    /// `--modified-since` and `--modified-before`.
+    pub fn accepts_chunk_timestamp(&self, timestamp: u32) -> bool {
+        let timestamp = u64::from(timestamp);
+        self.modified_since.is_none_or(|since| timestamp >= since)
+            && self.modified_before.is_none_or(|before| timestamp < before)
+    }
+
+    /// Whether matched items are counted per chunk, for heatmaps and the region cache.
+    pub fn counts_chunks(&self) -> bool {
+        self.heatmap.is_some() || self.cache_dir.is_some()
+    }
+
+    /// Whether matched items are counted, which the `ndjson` format skips unless they are
+    /// also saved to a snapshot.
+    pub fn counts_items(&self) -> bool {
+        !self.streams_ndjson() || self.save_snapshot.is_some()
+    }
+
+    /// Whether matched items are streamed to stdout instead of being counted.
+    pub fn streams_ndjson(&self) -> bool {
+        self.format == OutputFormat::Ndjson && !self.trades
+    }
 }
 
 /// Which summary‐format to display.
@@ -65,6 +284,70 @@ pub enum ViewMode {
 
     /// Summarize counts by NBT only
     ByNbt,
+
+    /// Summarize counts per enchantment and level
+    ByEnchantment,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compare the matched items of two worlds or snapshots
+    #[command(
+        long_about = "Compare the matched items of two worlds or snapshots, per dimension, data type and item.\nItem filters, --view detailed (to compare NBT too) and --format table, json, pretty-json, csv or tsv apply too.\n\nExample:\n\nnbt-sniffer diff backup/world world --item diamond_block --by-location --min-change 10"
+    )]
+    Diff(DiffArgs),
+
+    /// Count matched items in every backup of a folder, oldest first
+    #[command(
+        long_about = "Count matched items in every backup of a folder, oldest first, with one row per backup and item.\nBackups are world directories or .zip, .tar, .tar.gz or .tgz archives, ordered by modification time.\nFiles unchanged since the previous backup are not scanned again.\nItem filters, --view detailed (to tell items apart by NBT too) and --format table, json, pretty-json, csv or tsv apply too.\n\nExample:\n\nnbt-sniffer history backups/ --item diamond netherite_ingot --format csv"
+    )]
+    History(HistoryArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    /// Folder holding the backups, as world directories or archives
+    #[arg(value_name = "DIR")]
+    pub backups: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// World directory or snapshot file to compare from
+    #[arg(value_name = "OLD")]
+    pub old: PathBuf,
+
+    /// World directory or snapshot file to compare to
+    #[arg(value_name = "NEW")]
+    pub new: PathBuf,
+
+    /// Compare items per holder (container, entity or player) instead of per dimension and data type
+    #[arg(
+        long,
+        long_help = "Compare items per holder (container, entity or player) and position instead of per dimension and data type.\nSnapshots must have been saved with --snapshot-locations."
+    )]
+    pub by_location: bool,
+
+    /// Hide rows whose count changed by less than N
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub min_change: u64,
+
+    /// Hide rows whose count changed by less than PERCENT percent of the old count
+    #[arg(long, value_name = "PERCENT")]
+    pub min_change_percent: Option<f64>,
+
+    /// Also list items whose count did not change
+    #[arg(long)]
+    pub show_unchanged: bool,
+}
+
+/// Area covered by one heatmap cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HeatmapResolution {
+    /// One cell per 16x16 chunk
+    Chunk,
+    /// One cell per 512x512 region file
+    Region,
 }
 
 /// Which output format to use for the summary tables.
@@ -73,59 +356,186 @@ pub enum OutputFormat {
     Table,
     Json,
     PrettyJson,
+    /// Comma-separated rows, one per item and report section
+    Csv,
+    /// Tab-separated rows, one per item and report section
+    Tsv,
+    /// One JSON object per matched item stack, streamed while scanning
+    Ndjson,
+    /// A single self-contained HTML page with sortable tables
+    Html,
+    /// GitHub-flavored Markdown tables and lists
+    Markdown,
 }
 
 impl OutputFormat {
     pub fn is_json(&self) -> bool {
         matches!(self, OutputFormat::Json | OutputFormat::PrettyJson)
     }
+
+    /// Whether per-source trees are part of the report document, instead of being printed
+    /// while scanning.
+    pub fn embeds_source_trees(&self) -> bool {
+        matches!(self, OutputFormat::Html | OutputFormat::Markdown)
+    }
+
+    /// The field delimiter of the delimited-text formats.
+    pub fn delimiter(&self) -> Option<u8> {
+        match self {
+            OutputFormat::Csv => Some(b','),
+            OutputFormat::Tsv => Some(b'\t'),
+            _ => None,
+        }
+    }
 }
 
 /// Represents a query for an item and its optional NBT filters
 #[derive(Debug)]
 pub struct ItemFilter {
     pub id: Option<String>,
+    /// Item tag (without the leading `#`) the item ID must belong to
+    pub tag: Option<String>,
+    /// Item IDs contained in `tag`, filled in by `tags::resolve_item_filter_tags`
+    pub tag_members: Option<HashSet<String>>,
     pub required_nbt: Option<Value>,
 }
 
+impl ItemFilter {
+    /// Returns `true` if `id` satisfies both the ID and the tag constraint of this filter.
+    /// An unresolved tag matches nothing.
+    pub fn matches_id(&self, id: &str) -> bool {
+        let id_ok = self.id.as_ref().is_none_or(|qid| qid == id);
+        let tag_ok = self.tag.is_none()
+            || self
+                .tag_members
+                .as_ref()
+                .is_some_and(|members| members.contains(id));
+        id_ok && tag_ok
+    }
+}
+
 /// Parse raw CLI `item` arguments into `ItemFilter` structs
-/// Each entry is of form `ITEM_ID{nbt}`
+/// Each entry is of form `ITEM_ID{nbt}` or `#TAG_ID{nbt}`
 pub fn parse_item_args(raw_items: &[String]) -> Vec<ItemFilter> {
     raw_items
         .iter()
-        .map(|entry| {
-            let mut id_str = entry.as_str();
-            let mut nbt_query = None;
-
-            if let Some(start) = entry.find('{')
-                && let Some(end) = entry.rfind('}')
-            {
-                id_str = &entry[..start];
-                let nbt_str = &entry[start..=end];
-                if !nbt_str.is_empty() {
-                    match valence_nbt::snbt::from_snbt_str(nbt_str) {
-                        Ok(parsed) => nbt_query = Some(parsed),
-                        Err(e) => eprintln!("Failed to parse SNBT '{nbt_str}': {e}"),
-                    }
-                }
-            }
-
-            let id = if id_str.is_empty() {
-                None
-            } else if id_str.contains(':') {
-                Some(id_str.to_string())
-            } else {
-                Some(format!("minecraft:{id_str}"))
-            };
-
-            ItemFilter {
-                id,
-                required_nbt: nbt_query,
-            }
-        })
+        .map(|entry| parse_item_arg(entry))
         .collect()
 }
 
+/// Parse a single item spec of form `ITEM_ID{nbt}` or `#TAG_ID{nbt}`.
+/// Invalid SNBT is reported and ignored, matching the item by ID only.
+pub fn parse_item_arg(entry: &str) -> ItemFilter {
+    let (id_str, nbt_str) = split_item_arg(entry);
+    let nbt_query = nbt_str.and_then(|nbt_str| match valence_nbt::snbt::from_snbt_str(nbt_str) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            eprintln!("Failed to parse SNBT '{nbt_str}': {e}");
+            None
+        }
+    });
+    item_filter(id_str, nbt_query)
+}
+
+/// Same as `parse_item_arg`, failing on invalid SNBT instead of ignoring it.
+pub fn try_parse_item_arg(entry: &str) -> Result<ItemFilter, String> {
+    let (id_str, nbt_str) = split_item_arg(entry);
+    let nbt_query = nbt_str
+        .map(|nbt_str| {
+            valence_nbt::snbt::from_snbt_str(nbt_str)
+                .map_err(|e| format!("invalid SNBT '{nbt_str}': {e}"))
+        })
+        .transpose()?;
+    Ok(item_filter(id_str, nbt_query))
+}
+
+/// Splits an item spec into its ID or tag and its SNBT, if any.
+fn split_item_arg(entry: &str) -> (&str, Option<&str>) {
+    if let Some(start) = entry.find('{')
+        && let Some(end) = entry.rfind('}')
+    {
+        (&entry[..start], Some(&entry[start..=end]))
+    } else {
+        (entry, None)
+    }
+}
+
+fn item_filter(id_str: &str, nbt_query: Option<Value>) -> ItemFilter {
+    let (id_str, is_tag) = match id_str.strip_prefix('#') {
+        Some(tag_str) => (tag_str, true),
+        None => (id_str, false),
+    };
+
+    let id = if id_str.is_empty() {
+        None
+    } else if id_str.contains(':') {
+        Some(id_str.to_string())
+    } else {
+        Some(format!("minecraft:{id_str}"))
+    };
+
+    let (id, tag) = if is_tag { (None, id) } else { (id, None) };
+
+    ItemFilter {
+        id,
+        tag,
+        tag_members: None,
+        required_nbt: nbt_query,
+    }
+}
+
+/// Latest year accepted by `parse_date`.
+const MAX_DATE_YEAR: u64 = 9999;
+
+/// Parse a UTC date, `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM[:SS][Z]` (a space may replace the
+/// `T`), or a number of seconds since the Unix epoch, into seconds since the Unix epoch
+pub fn parse_date(value: &str) -> Result<u64, String> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    let invalid =
+        || format!("invalid date '{value}', expected YYYY-MM-DD, YYYY-MM-DDTHH:MM[:SS] or seconds");
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let number = |part: &str| part.parse::<u64>().map_err(|_| invalid());
+
+    let date_parts: Vec<_> = date.split('-').collect();
+    let [year, month, day] = date_parts[..] else {
+        return Err(invalid());
+    };
+    let (year, month, day) = (number(year)?, number(month)?, number(day)?);
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return Err(invalid()),
+    };
+    if !(1970..=MAX_DATE_YEAR).contains(&year) || day == 0 || day > days_in_month {
+        return Err(invalid());
+    }
+
+    let mut seconds_of_day = 0;
+    if let Some(time) = time {
+        let time_parts: Vec<_> = time.split(':').collect();
+        let (hours, minutes, seconds) = match time_parts[..] {
+            [hours, minutes] => (number(hours)?, number(minutes)?, 0),
+            [hours, minutes, seconds] => (number(hours)?, number(minutes)?, number(seconds)?),
+            _ => return Err(invalid()),
+        };
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(invalid());
+        }
+        seconds_of_day = hours * 3_600 + minutes * 60 + seconds;
+    }
+
+    let days = civil_date::days_from_civil(year as i64, month as u32, day as u32);
+    Ok(days as u64 * 86_400 + seconds_of_day)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,6 +607,78 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_try_parse_item_arg_rejects_invalid_nbt() {
+        assert!(try_parse_item_arg("iron_ingot{invalid_nbt:}").is_err());
+        let filter = try_parse_item_arg("iron_ingot{a:1b}").unwrap();
+        assert_eq!(filter.id, Some("minecraft:iron_ingot".to_string()));
+        assert!(filter.required_nbt.is_some());
+    }
+
+    #[test]
+    fn test_all_conflicts_with_item_filters() {
+        use clap::Parser;
+        assert!(
+            CliArgs::try_parse_from(["nbt-sniffer", "-w", "w", "--all", "--item", "diamond"])
+                .is_err()
+        );
+        assert!(
+            CliArgs::try_parse_from(["nbt-sniffer", "-w", "w", "--all", "--filter", "diamond"])
+                .is_err()
+        );
+        assert!(
+            CliArgs::try_parse_from([
+                "nbt-sniffer",
+                "-w",
+                "w",
+                "--item",
+                "diamond",
+                "--filter",
+                "not dirt"
+            ])
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_parse_item_args_tag() {
+        let args = vec!["#logs".to_string(), "#custom:gems{a:1b}".to_string()];
+        let filters = parse_item_args(&args);
+        assert_eq!(filters.len(), 2);
+        assert!(filters[0].id.is_none());
+        assert_eq!(filters[0].tag, Some("minecraft:logs".to_string()));
+        assert_eq!(filters[1].tag, Some("custom:gems".to_string()));
+        assert_eq!(
+            filters[1].required_nbt,
+            Some(compound! { "a" => 1i8 }.into())
+        );
+    }
+
+    #[test]
+    fn test_item_filter_matches_id_with_tag() {
+        let mut filter = parse_item_args(&["#minecraft:logs".to_string()]).remove(0);
+        assert!(!filter.matches_id("minecraft:oak_log"), "unresolved tag");
+
+        filter.tag_members = Some(HashSet::from(["minecraft:oak_log".to_string()]));
+        assert!(filter.matches_id("minecraft:oak_log"));
+        assert!(!filter.matches_id("minecraft:diamond"));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2000-02-29"), Ok(951_782_400));
+        assert_eq!(parse_date("2024-06-15T12:30:45Z"), Ok(1_718_454_645));
+        assert_eq!(parse_date("2024-06-15 12:30"), Ok(1_718_454_600));
+        assert_eq!(parse_date("1718454645"), Ok(1_718_454_645));
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2024-06-15T24:00").is_err());
+        assert!(parse_date("yesterday").is_err());
+        assert_eq!(parse_date("9999-12-31"), Ok(253_402_214_400));
+        assert!(parse_date("10000-01-01").is_err());
+        assert!(parse_date("18446744073709551615-01-01").is_err());
+    }
+
     #[test]
     fn test_parse_item_args_multiple_items() {
         let args = vec![
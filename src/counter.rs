@@ -1,35 +1,201 @@
-use std::{collections::HashMap, fmt};
-
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::{Arc, OnceLock},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use simdnbt::borrow::NbtCompound;
 use valence_nbt::Value;
 
-use crate::{Scope, escape_nbt_string};
+use crate::{
+    Scope,
+    enchantments::{Enchantment, component_enchantments},
+    escape_nbt_string, nbt_utils,
+};
+
+/// The components of a counted item. Keyed by the hash of their SNBT, computed from the
+/// raw NBT while scanning, and backed by one converted representative shared by every key
+/// with the same hash. The SNBT itself is written only once a view asks for it.
+#[derive(Clone)]
+pub struct ItemComponents {
+    hash: u128,
+    shared: Arc<SharedComponents>,
+}
+
+struct SharedComponents {
+    nbt: Value,
+    snbt: OnceLock<String>,
+}
+
+impl ItemComponents {
+    pub fn new(nbt: Value) -> Self {
+        let snbt = valence_nbt::snbt::to_snbt_string(&nbt);
+        Self {
+            hash: nbt_utils::snbt_hash(&snbt),
+            shared: Arc::new(SharedComponents {
+                nbt,
+                snbt: OnceLock::from(snbt),
+            }),
+        }
+    }
+
+    pub fn nbt(&self) -> &Value {
+        &self.shared.nbt
+    }
+
+    pub fn snbt(&self) -> &str {
+        self.shared
+            .snbt
+            .get_or_init(|| valence_nbt::snbt::to_snbt_string(&self.shared.nbt))
+    }
+}
+
+impl PartialEq for ItemComponents {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl Eq for ItemComponents {}
+
+impl Hash for ItemComponents {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u128(self.hash);
+    }
+}
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+impl PartialOrd for ItemComponents {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ItemComponents {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        self.snbt()
+            .cmp(other.snbt())
+            .then_with(|| self.hash.cmp(&other.hash))
+    }
+}
+
+impl fmt::Debug for ItemComponents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.snbt(), f)
+    }
+}
+
+impl Serialize for ItemComponents {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.snbt())
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemComponents {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snbt = String::deserialize(deserializer)?;
+        valence_nbt::snbt::from_snbt_str(&snbt)
+            .map(Self::new)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The components met while scanning a task, by hash, so that items with the same
+/// components share one converted representative.
+#[derive(Debug, Default)]
+pub struct ComponentsInterner {
+    components: HashMap<u128, ItemComponents>,
+}
+
+impl ComponentsInterner {
+    /// The components equal to `raw`, converted only the first time they are met.
+    pub fn intern(&mut self, raw: &NbtCompound) -> ItemComponents {
+        let hash = nbt_utils::simdnbt_snbt_hash(raw);
+        self.components
+            .entry(hash)
+            .or_insert_with(|| ItemComponents {
+                hash,
+                shared: Arc::new(SharedComponents {
+                    nbt: nbt_utils::convert_simdnbt_to_valence_nbt(raw),
+                    snbt: OnceLock::new(),
+                }),
+            })
+            .clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ItemKey {
     pub id: String,
-    pub components_snbt: Option<String>,
+    /// Serialized as SNBT, under the name used before components were kept as NBT
+    #[serde(
+        default,
+        rename = "components_snbt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub components: Option<ItemComponents>,
+    /// Nesting depth (0 for loose items), only tracked with `--group-by-depth`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
 }
 
 impl ItemKey {
     pub fn new(id: String, components_nbt: Option<&Value>) -> Self {
+        Self::with_components(id, components_nbt.cloned().map(ItemComponents::new))
+    }
+
+    pub fn with_components(id: String, components: Option<ItemComponents>) -> Self {
         ItemKey {
             id,
-            components_snbt: components_nbt.map(valence_nbt::snbt::to_snbt_string),
+            components,
+            depth: None,
         }
     }
+
+    pub fn with_depth(mut self, depth: Option<u32>) -> Self {
+        self.depth = depth;
+        self
+    }
 }
 
 impl fmt::Display for ItemKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match &self.components_snbt {
-            Some(snbt) => write!(f, "{} {}", self.id, escape_nbt_string(snbt)),
+        match &self.components {
+            Some(components) => write!(f, "{} {}", self.id, escape_nbt_string(components.snbt())),
             None => write!(f, "{}", self.id),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Serializes a map as a list of `[key, value]` pairs, since JSON object keys must be strings.
+mod entries {
+    use super::*;
+
+    pub fn serialize<K: Serialize, V: Serialize, S: Serializer>(
+        map: &HashMap<K, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Vec::<(K, V)>::deserialize(deserializer).map(|entries| entries.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Counter {
+    #[serde(with = "entries")]
     counts: HashMap<ItemKey, u64>,
 }
 
@@ -41,7 +207,20 @@ impl Counter {
     }
 
     pub fn add(&mut self, id: String, components_nbt: Option<&Value>, count: u64) {
-        let key = ItemKey::new(id, components_nbt);
+        self.add_at_depth(id, components_nbt, None, count);
+    }
+
+    pub fn add_at_depth(
+        &mut self,
+        id: String,
+        components_nbt: Option<&Value>,
+        depth: Option<u32>,
+        count: u64,
+    ) {
+        self.add_key(ItemKey::new(id, components_nbt).with_depth(depth), count);
+    }
+
+    pub fn add_key(&mut self, key: ItemKey, count: u64) {
         *self.counts.entry(key).or_insert(0) += count;
     }
 
@@ -51,6 +230,19 @@ impl Counter {
         }
     }
 
+    /// Same as `merge`, moving the keys of `other` instead of cloning them.
+    pub fn append(&mut self, other: Self) {
+        let (mut larger, smaller) = if self.counts.len() >= other.counts.len() {
+            (std::mem::take(&mut self.counts), other.counts)
+        } else {
+            (other.counts, std::mem::take(&mut self.counts))
+        };
+        for (key, count) in smaller {
+            *larger.entry(key).or_insert(0) += count;
+        }
+        self.counts = larger;
+    }
+
     pub fn total(&self) -> u64 {
         self.counts.values().sum()
     }
@@ -63,14 +255,37 @@ impl Counter {
         totals
     }
 
-    pub fn total_by_nbt(&self) -> HashMap<Option<String>, u64> {
+    pub fn total_by_id_and_depth(&self) -> HashMap<(String, Option<u32>), u64> {
+        let mut totals = HashMap::new();
+        for (key, &count) in &self.counts {
+            *totals.entry((key.id.clone(), key.depth)).or_insert(0) += count;
+        }
+        totals
+    }
+
+    pub fn total_by_nbt(&self) -> HashMap<Option<&ItemComponents>, u64> {
         let mut m = HashMap::new();
         for (key, &cnt) in &self.counts {
-            *m.entry(key.components_snbt.clone()).or_insert(0) += cnt;
+            *m.entry(key.components.as_ref()).or_insert(0) += cnt;
         }
         m
     }
 
+    /// Counts items per enchantment, target and level. An item with several enchantments
+    /// is counted once for each of them.
+    pub fn total_by_enchantment(&self) -> HashMap<Enchantment, u64> {
+        let mut totals = HashMap::new();
+        for (key, &count) in &self.counts {
+            let Some(components) = &key.components else {
+                continue;
+            };
+            for enchantment in component_enchantments(components.nbt()) {
+                *totals.entry(enchantment).or_insert(0) += count;
+            }
+        }
+        totals
+    }
+
     pub fn detailed_counts(&self) -> &HashMap<ItemKey, u64> {
         &self.counts
     }
@@ -82,8 +297,9 @@ impl Default for Counter {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CounterMap {
+    #[serde(with = "entries")]
     map: HashMap<Scope, Counter>,
 }
 
@@ -102,6 +318,13 @@ impl CounterMap {
         self.entry_counter(scope).merge(other);
     }
 
+    /// Same as merging every scope of `other`, moving its keys instead of cloning them.
+    pub fn append(&mut self, other: Self) {
+        for (scope, counter) in other.map {
+            self.entry_counter(scope).append(counter);
+        }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&Scope, &Counter)> {
         self.map.iter()
     }
@@ -136,13 +359,13 @@ mod tests {
     fn item_key_new_and_display() {
         let key1 = ItemKey::new("minecraft:diamond".to_string(), None);
         assert_eq!(key1.id, "minecraft:diamond");
-        assert!(key1.components_snbt.is_none());
+        assert!(key1.components.is_none());
         assert_eq!(format!("{key1}"), "minecraft:diamond");
 
         let nbt = nbt_val("{components:{\"minecraft:enchantments\":{\"minecraft:sharpness\":1s}}}");
         let key2 = ItemKey::new("minecraft:sword".to_string(), Some(&nbt));
         assert_eq!(key2.id, "minecraft:sword");
-        assert!(key2.components_snbt.is_some());
+        assert!(key2.components.is_some());
         assert_eq!(
             format!("{key2}"),
             "minecraft:sword {components:{\"minecraft:enchantments\":{\"minecraft:sharpness\":1s}}}"
@@ -192,6 +415,66 @@ mod tests {
         assert_eq!(counter1.detailed_counts().get(&key_wood), Some(&15));
     }
 
+    #[test]
+    fn counter_append_moves_keys() {
+        let nbt = nbt_val("{\"minecraft:map_id\":7}");
+        let mut counter1 = Counter::new();
+        counter1.add("minecraft:filled_map".to_string(), Some(&nbt), 1);
+        let mut counter2 = Counter::new();
+        counter2.add("minecraft:filled_map".to_string(), Some(&nbt), 2);
+        counter2.add("minecraft:paper".to_string(), None, 3);
+        let shared = counter2
+            .detailed_counts()
+            .keys()
+            .find_map(|key| key.components.clone());
+
+        counter1.append(counter2);
+        let key = ItemKey::new("minecraft:filled_map".to_string(), Some(&nbt));
+        assert_eq!(counter1.detailed_counts().get(&key), Some(&3));
+        assert_eq!(counter1.total(), 6);
+        let stored = counter1
+            .detailed_counts()
+            .keys()
+            .find_map(|key| key.components.clone())
+            .unwrap();
+        assert!(Arc::ptr_eq(&stored.shared, &shared.unwrap().shared));
+    }
+
+    #[test]
+    fn components_serialize_as_snbt() {
+        let key = ItemKey::new(
+            "minecraft:book".to_string(),
+            Some(&nbt_val("{\"minecraft:custom_data\":{id:\"a b\"}}")),
+        );
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":"minecraft:book","components_snbt":"{\"minecraft:custom_data\":{id:\"a b\"}}"}"#
+        );
+        assert_eq!(serde_json::from_str::<ItemKey>(&json).unwrap(), key);
+    }
+
+    #[test]
+    fn interned_components_match_converted_ones() {
+        let bytes = crate::nbt_utils::snbt_to_binary(
+            r#"{"minecraft:custom_name":"Sword","minecraft:damage":3,"minecraft:lore":["a","b"]}"#,
+        );
+        let nbt = simdnbt::borrow::read(&mut std::io::Cursor::new(&bytes[..]))
+            .unwrap()
+            .unwrap();
+        let mut interner = ComponentsInterner::default();
+        let first = interner.intern(&nbt.as_compound());
+        let second = interner.intern(&nbt.as_compound());
+        assert!(Arc::ptr_eq(&first.shared, &second.shared));
+
+        let converted = ItemComponents::new(nbt_utils::convert_simdnbt_to_valence_nbt(
+            &nbt.as_compound(),
+        ));
+        assert_eq!(first, converted);
+        assert_eq!(first.snbt(), converted.snbt());
+        assert_ne!(first, ItemComponents::new(nbt_val("{}")));
+    }
+
     #[test]
     fn counter_total_by_id() {
         let mut counter = Counter::new();
@@ -224,13 +507,52 @@ mod tests {
         counter.add("item3".to_string(), Some(&nbt_b_val), 20);
         counter.add("item4".to_string(), None, 7);
 
-        let totals_by_nbt = counter.total_by_nbt();
+        let totals_by_nbt: HashMap<_, _> = counter
+            .total_by_nbt()
+            .into_iter()
+            .map(|(components, count)| (components.map(|c| c.snbt().to_string()), count))
+            .collect();
         assert_eq!(totals_by_nbt.get(&Some(nbt_a_canonical_snbt)), Some(&15));
         assert_eq!(totals_by_nbt.get(&Some(nbt_b_canonical_snbt)), Some(&20));
         assert_eq!(totals_by_nbt.get(&None), Some(&7));
         assert_eq!(totals_by_nbt.len(), 3);
     }
 
+    #[test]
+    fn counter_total_by_id_and_depth() {
+        let mut counter = Counter::new();
+        counter.add_at_depth("minecraft:diamond".to_string(), None, Some(0), 5);
+        counter.add_at_depth("minecraft:diamond".to_string(), None, Some(1), 64);
+        counter.add_at_depth("minecraft:diamond".to_string(), None, Some(1), 1);
+
+        let totals = counter.total_by_id_and_depth();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[&("minecraft:diamond".to_string(), Some(1))], 65);
+        assert_eq!(counter.total_by_id()["minecraft:diamond"], 70);
+    }
+
+    #[test]
+    fn counter_total_by_enchantment() {
+        let mut counter = Counter::new();
+        let sword = nbt_val(
+            r#"{"minecraft:enchantments":{"minecraft:sharpness":5,"minecraft:mending":1}}"#,
+        );
+        let book = nbt_val(r#"{"minecraft:stored_enchantments":{"minecraft:mending":1}}"#);
+        counter.add("minecraft:diamond_sword".to_string(), Some(&sword), 2);
+        counter.add("minecraft:enchanted_book".to_string(), Some(&book), 3);
+        counter.add("minecraft:stone".to_string(), None, 64);
+
+        let totals = counter.total_by_enchantment();
+        let mending_on_book = Enchantment {
+            id: "minecraft:mending".to_string(),
+            level: 1,
+            target: crate::enchantments::EnchantmentTarget::Book,
+        };
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals[&mending_on_book], 3);
+        assert_eq!(totals.values().sum::<u64>(), 2 + 2 + 3);
+    }
+
     #[test]
     fn counter_map_entry_and_merge_scope() {
         let mut map = CounterMap::new();
@@ -0,0 +1,331 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::Serialize;
+
+use crate::{DataType, ScanResult, cli::DiffArgs, counter::CounterMap, occurrences::Occurrence};
+
+const OVERWORLD: &str = "overworld";
+
+/// Whether the count of an item went up, down or stayed the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DiffStatus {
+    Gained,
+    Lost,
+    Unchanged,
+}
+
+/// The holder of compared items, with `diff --by-location`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct DiffLocation {
+    pub holder: String,
+    pub position: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub player: Option<String>,
+}
+
+/// The counts of one item in both worlds, for one scope or location.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffRow {
+    pub dimension: String,
+    pub data_type: DataType,
+    #[serde(flatten)]
+    pub location: Option<DiffLocation>,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbt: Option<String>,
+    pub old_count: u64,
+    pub new_count: u64,
+    pub change: i64,
+    pub status: DiffStatus,
+}
+
+/// Totals over every compared item, including those hidden by thresholds.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiffSummary {
+    pub old_total: u64,
+    pub new_total: u64,
+    /// Sum of the increases of the items that were gained
+    pub gained: u64,
+    /// Sum of the decreases of the items that were lost
+    pub lost: u64,
+    pub unchanged_rows: usize,
+    pub hidden_rows: usize,
+}
+
+type DiffKey = (
+    String,
+    DataType,
+    Option<DiffLocation>,
+    String,
+    Option<String>,
+);
+
+fn into_rows(counts: BTreeMap<DiffKey, [u64; 2]>) -> Vec<DiffRow> {
+    counts
+        .into_iter()
+        .map(
+            |((dimension, data_type, location, id, nbt), [old_count, new_count])| {
+                let change = new_count as i64 - old_count as i64;
+                DiffRow {
+                    dimension,
+                    data_type,
+                    location,
+                    id,
+                    nbt,
+                    old_count,
+                    new_count,
+                    change,
+                    status: match change {
+                        0 => DiffStatus::Unchanged,
+                        c if c > 0 => DiffStatus::Gained,
+                        _ => DiffStatus::Lost,
+                    },
+                }
+            },
+        )
+        .collect()
+}
+
+/// Renames the dimensions named after the world folder, the overworld and its player data,
+/// to `overworld`, so that copies of a world under other names can be compared.
+pub fn normalize_overworld(mut result: ScanResult, world_path: &Path) -> ScanResult {
+    let Some(world_name) = world_path.file_name().and_then(|name| name.to_str()) else {
+        return result;
+    };
+    let rename = |dimension: &mut String| {
+        if let Some(rest) = dimension.strip_prefix(world_name)
+            && (rest.is_empty() || rest.starts_with('/'))
+        {
+            *dimension = format!("{OVERWORLD}{rest}");
+        }
+    };
+    let mut counter_map = CounterMap::new();
+    for (scope, counter) in result.counter_map.iter() {
+        let mut scope = scope.clone();
+        rename(&mut scope.dimension);
+        counter_map.merge_scope(scope, counter);
+    }
+    result.counter_map = counter_map;
+    for occurrence in &mut result.occurrences {
+        rename(&mut occurrence.dimension);
+    }
+    result
+}
+
+/// Compares item counts per dimension and data type. Items are told apart by ID, and by
+/// NBT too when `with_nbt`; depths are merged.
+pub fn diff_counts(old: &CounterMap, new: &CounterMap, with_nbt: bool) -> Vec<DiffRow> {
+    let mut counts: BTreeMap<DiffKey, [u64; 2]> = BTreeMap::new();
+    for (side, counter_map) in [old, new].into_iter().enumerate() {
+        for (scope, counter) in counter_map.iter() {
+            for (key, &count) in counter.detailed_counts() {
+                let nbt = key
+                    .components
+                    .as_ref()
+                    .filter(|_| with_nbt)
+                    .map(|components| components.snbt().to_string());
+                let diff_key = (
+                    scope.dimension.clone(),
+                    scope.data_type,
+                    None,
+                    key.id.clone(),
+                    nbt,
+                );
+                counts.entry(diff_key).or_default()[side] += count;
+            }
+        }
+    }
+    into_rows(counts)
+}
+
+/// Compares item counts per holder, from the matched items of both worlds.
+pub fn diff_locations(old: &[Occurrence], new: &[Occurrence], with_nbt: bool) -> Vec<DiffRow> {
+    let mut counts: BTreeMap<DiffKey, [u64; 2]> = BTreeMap::new();
+    for (side, occurrences) in [old, new].into_iter().enumerate() {
+        for occurrence in occurrences {
+            let location = DiffLocation {
+                holder: occurrence.holder.clone(),
+                position: occurrence.position.clone(),
+                player: occurrence.player.clone(),
+            };
+            let nbt = with_nbt
+                .then(|| {
+                    occurrence
+                        .components
+                        .as_ref()
+                        .map(valence_nbt::snbt::to_snbt_string)
+                })
+                .flatten();
+            let diff_key = (
+                occurrence.dimension.clone(),
+                occurrence.data_type,
+                Some(location),
+                occurrence.id.clone(),
+                nbt,
+            );
+            counts.entry(diff_key).or_default()[side] += occurrence.count;
+        }
+    }
+    into_rows(counts)
+}
+
+impl DiffArgs {
+    /// Whether `row` passes `--min-change`, `--min-change-percent` and `--show-unchanged`.
+    pub fn shows(&self, row: &DiffRow) -> bool {
+        if row.status == DiffStatus::Unchanged {
+            return self.show_unchanged;
+        }
+        let change = row.change.unsigned_abs();
+        // Items absent from the old world changed by an infinite percentage
+        let percent_ok = self.min_change_percent.is_none_or(|min_percent| {
+            row.old_count == 0 || change as f64 * 100.0 / row.old_count as f64 >= min_percent
+        });
+        change >= self.min_change && percent_ok
+    }
+}
+
+/// Sums up `rows` and keeps those shown by the thresholds of `args`.
+pub fn filter_rows(rows: Vec<DiffRow>, args: &DiffArgs) -> (Vec<DiffRow>, DiffSummary) {
+    let mut summary = DiffSummary::default();
+    let mut shown = Vec::new();
+    for row in rows {
+        summary.old_total += row.old_count;
+        summary.new_total += row.new_count;
+        match row.status {
+            DiffStatus::Gained => summary.gained += row.change.unsigned_abs(),
+            DiffStatus::Lost => summary.lost += row.change.unsigned_abs(),
+            DiffStatus::Unchanged => summary.unchanged_rows += 1,
+        }
+        if args.shows(&row) {
+            shown.push(row);
+        } else if row.status != DiffStatus::Unchanged {
+            summary.hidden_rows += 1;
+        }
+    }
+    (shown, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Scope, counter::Counter, occurrences::Holder};
+    use std::path::PathBuf;
+
+    fn diff_args(min_change: u64, min_change_percent: Option<f64>) -> DiffArgs {
+        DiffArgs {
+            old: PathBuf::from("old"),
+            new: PathBuf::from("new"),
+            by_location: false,
+            min_change,
+            min_change_percent,
+            show_unchanged: false,
+        }
+    }
+
+    fn counter_map(items: &[(&str, u64)]) -> CounterMap {
+        let mut counter = Counter::new();
+        for &(id, count) in items {
+            counter.add(id.to_string(), None, count);
+        }
+        let mut map = CounterMap::new();
+        map.merge_scope(
+            Scope {
+                dimension: "overworld".to_string(),
+                data_type: DataType::BlockEntity,
+            },
+            &counter,
+        );
+        map
+    }
+
+    #[test]
+    fn compares_counts_and_applies_thresholds() {
+        let old = counter_map(&[("minecraft:diamond", 100), ("minecraft:dirt", 5)]);
+        let new = counter_map(&[
+            ("minecraft:diamond", 73),
+            ("minecraft:dirt", 5),
+            ("minecraft:elytra", 1),
+        ]);
+        let rows = diff_counts(&old, &new, false);
+        let statuses: Vec<_> = rows.iter().map(|r| (r.id.as_str(), r.change)).collect();
+        assert_eq!(
+            statuses,
+            [
+                ("minecraft:diamond", -27),
+                ("minecraft:dirt", 0),
+                ("minecraft:elytra", 1)
+            ]
+        );
+
+        let (shown, summary) = filter_rows(rows.clone(), &diff_args(10, None));
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].status, DiffStatus::Lost);
+        assert_eq!((summary.gained, summary.lost), (1, 27));
+        assert_eq!((summary.unchanged_rows, summary.hidden_rows), (1, 1));
+
+        let (shown, _) = filter_rows(rows, &diff_args(1, Some(50.0)));
+        let ids: Vec<_> = shown.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["minecraft:elytra"]);
+    }
+
+    #[test]
+    fn normalizes_world_folder_dimensions() {
+        let mut counter = Counter::new();
+        counter.add("minecraft:diamond".to_string(), None, 1);
+        let mut result = ScanResult::new();
+        for dimension in ["backup-1", "backup-1/playerdata", "DIM-1", "backup-10"] {
+            let scope = Scope {
+                dimension: dimension.to_string(),
+                data_type: DataType::BlockEntity,
+            };
+            result.counter_map.merge_scope(scope, &counter);
+        }
+        let result = normalize_overworld(result, Path::new("/saves/backup-1"));
+        let mut dimensions: Vec<_> = result
+            .counter_map
+            .iter()
+            .map(|(scope, _)| scope.dimension.clone())
+            .collect();
+        dimensions.sort();
+        assert_eq!(
+            dimensions,
+            ["DIM-1", "backup-10", "overworld", "overworld/playerdata"]
+        );
+    }
+
+    #[test]
+    fn compares_items_per_holder() {
+        let scope = Scope {
+            dimension: "overworld".to_string(),
+            data_type: DataType::BlockEntity,
+        };
+        let chest = |position| Holder {
+            scope: &scope,
+            id: "minecraft:chest",
+            position,
+            coordinates: None,
+            player: None,
+        };
+        let diamonds = |position, count| {
+            Occurrence::new(
+                &chest(position),
+                None,
+                &[],
+                None,
+                "minecraft:diamond_block".to_string(),
+                count,
+                None,
+            )
+        };
+        let old = [diamonds("100 64 -20", 64), diamonds("100 64 -20", 27)];
+        let new = [diamonds("100 64 -20", 64), diamonds("0 70 0", 27)];
+        let rows = diff_locations(&old, &new, false);
+        let changes: Vec<_> = rows
+            .iter()
+            .map(|r| (r.location.as_ref().unwrap().position.as_str(), r.change))
+            .collect();
+        assert_eq!(changes, [("0 70 0", 27), ("100 64 -20", -27)]);
+    }
+}
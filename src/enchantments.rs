@@ -0,0 +1,294 @@
+use serde::Serialize;
+use valence_nbt::Value;
+
+use crate::{nbt_integer_value, tags::normalize_id};
+
+/// Where an enchantment is stored on an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnchantmentTarget {
+    /// Applied to the item itself (`minecraft:enchantments`)
+    Item,
+    /// Stored in an enchanted book (`minecraft:stored_enchantments`)
+    Book,
+}
+
+impl EnchantmentTarget {
+    pub fn label(self) -> &'static str {
+        match self {
+            EnchantmentTarget::Item => "Item",
+            EnchantmentTarget::Book => "Book",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Enchantment {
+    pub id: String,
+    pub level: i64,
+    pub target: EnchantmentTarget,
+}
+
+const COMPONENT_KEYS: [(&str, EnchantmentTarget); 2] = [
+    ("minecraft:enchantments", EnchantmentTarget::Item),
+    ("minecraft:stored_enchantments", EnchantmentTarget::Book),
+];
+
+const LEGACY_KEYS: [(&str, EnchantmentTarget); 2] = [
+    ("Enchantments", EnchantmentTarget::Item),
+    ("StoredEnchantments", EnchantmentTarget::Book),
+];
+
+/// Lists the enchantments of an item given its full NBT, reading the `components`
+/// compound as well as the `tag` compound used before 1.20.5.
+pub fn item_enchantments(item_nbt: &Value) -> Vec<Enchantment> {
+    let Value::Compound(item) = item_nbt else {
+        return Vec::new();
+    };
+    let mut enchantments = item
+        .get("components")
+        .map(component_enchantments)
+        .unwrap_or_default();
+
+    if let Some(Value::Compound(tag)) = item.get("tag") {
+        for (key, target) in LEGACY_KEYS {
+            let Some(Value::List(list)) = tag.get(key) else {
+                continue;
+            };
+            for entry in list.iter() {
+                let Value::Compound(entry) = entry.to_value() else {
+                    continue;
+                };
+                if let (Some(Value::String(id)), Some(level)) = (
+                    entry.get("id"),
+                    entry.get("lvl").and_then(nbt_integer_value),
+                ) {
+                    enchantments.push(Enchantment {
+                        id: normalize_id(id),
+                        level,
+                        target,
+                    });
+                }
+            }
+        }
+    }
+    enchantments
+}
+
+/// Lists the enchantments found in an item `components` compound. Both the 1.21.5+ layout
+/// (`{"minecraft:sharpness":5}`) and the older `{levels:{...}}` wrapper are understood.
+pub fn component_enchantments(components: &Value) -> Vec<Enchantment> {
+    let Value::Compound(components) = components else {
+        return Vec::new();
+    };
+    let mut enchantments = Vec::new();
+    for (key, target) in COMPONENT_KEYS {
+        let Some(Value::Compound(component)) = components.get(key) else {
+            continue;
+        };
+        let levels = match component.get("levels") {
+            Some(Value::Compound(levels)) => levels,
+            _ => component,
+        };
+        for (id, level) in levels {
+            if let Some(level) = nbt_integer_value(level) {
+                enchantments.push(Enchantment {
+                    id: normalize_id(id),
+                    level,
+                    target,
+                });
+            }
+        }
+    }
+    enchantments
+}
+
+/// A filter on enchantments, parsed from `[item:|book:]ID[LEVEL]` where `LEVEL` is one of
+/// `=N`, `>=N`, `<=N`, `>N`, `<N` or `=MIN..MAX` (either bound may be omitted).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnchantmentPredicate {
+    pub id: String,
+    pub target: Option<EnchantmentTarget>,
+    pub min_level: Option<i64>,
+    pub max_level: Option<i64>,
+}
+
+impl EnchantmentPredicate {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (target, rest) = if let Some(rest) = spec.strip_prefix("item:") {
+            (Some(EnchantmentTarget::Item), rest)
+        } else if let Some(rest) = spec.strip_prefix("book:") {
+            (Some(EnchantmentTarget::Book), rest)
+        } else {
+            (None, spec)
+        };
+
+        let op_start = rest.find(['=', '<', '>']).unwrap_or(rest.len());
+        let id = rest[..op_start].trim();
+        if id.is_empty() {
+            return Err(format!("missing enchantment id in '{spec}'"));
+        }
+        if !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.:/".contains(c))
+        {
+            return Err(format!("invalid enchantment id '{id}'"));
+        }
+
+        let parse_level = |s: &str| {
+            s.trim()
+                .parse::<i64>()
+                .map_err(|_| format!("invalid enchantment level '{}' in '{spec}'", s.trim()))
+        };
+        let out_of_range = || format!("enchantment level out of range in '{spec}'");
+        let level = &rest[op_start..];
+        let (min_level, max_level) = if level.is_empty() {
+            (None, None)
+        } else if let Some(n) = level.strip_prefix(">=") {
+            (Some(parse_level(n)?), None)
+        } else if let Some(n) = level.strip_prefix("<=") {
+            (None, Some(parse_level(n)?))
+        } else if let Some(n) = level.strip_prefix('>') {
+            let n = parse_level(n)?.checked_add(1).ok_or_else(out_of_range)?;
+            (Some(n), None)
+        } else if let Some(n) = level.strip_prefix('<') {
+            let n = parse_level(n)?.checked_sub(1).ok_or_else(out_of_range)?;
+            (None, Some(n))
+        } else if let Some(range) = level.strip_prefix('=') {
+            match range.split_once("..") {
+                Some((min, max)) => (
+                    (!min.trim().is_empty())
+                        .then(|| parse_level(min))
+                        .transpose()?,
+                    (!max.trim().is_empty())
+                        .then(|| parse_level(max))
+                        .transpose()?,
+                ),
+                None => {
+                    let n = parse_level(range)?;
+                    (Some(n), Some(n))
+                }
+            }
+        } else {
+            return Err(format!("invalid enchantment level in '{spec}'"));
+        };
+
+        Ok(Self {
+            id: normalize_id(id),
+            target,
+            min_level,
+            max_level,
+        })
+    }
+
+    pub fn matches(&self, item_nbt: &Value) -> bool {
+        item_enchantments(item_nbt)
+            .iter()
+            .any(|enchantment| self.matches_enchantment(enchantment))
+    }
+
+    fn matches_enchantment(&self, enchantment: &Enchantment) -> bool {
+        enchantment.id == self.id
+            && self
+                .target
+                .is_none_or(|target| target == enchantment.target)
+            && self.min_level.is_none_or(|min| enchantment.level >= min)
+            && self.max_level.is_none_or(|max| enchantment.level <= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence_nbt::snbt::from_snbt_str;
+
+    #[test]
+    fn reads_all_enchantment_layouts() {
+        let current = from_snbt_str(
+            r#"{components:{"minecraft:enchantments":{"minecraft:sharpness":5},"minecraft:stored_enchantments":{"minecraft:mending":1}}}"#,
+        )
+        .unwrap();
+        let mut found = item_enchantments(&current);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                Enchantment {
+                    id: "minecraft:mending".to_string(),
+                    level: 1,
+                    target: EnchantmentTarget::Book,
+                },
+                Enchantment {
+                    id: "minecraft:sharpness".to_string(),
+                    level: 5,
+                    target: EnchantmentTarget::Item,
+                },
+            ]
+        );
+
+        let wrapped = from_snbt_str(
+            r#"{components:{"minecraft:enchantments":{levels:{"minecraft:efficiency":4},show_in_tooltip:1b}}}"#,
+        )
+        .unwrap();
+        assert_eq!(item_enchantments(&wrapped)[0].level, 4);
+
+        let legacy =
+            from_snbt_str(r#"{tag:{StoredEnchantments:[{id:"minecraft:mending",lvl:1s}]}}"#)
+                .unwrap();
+        assert_eq!(
+            item_enchantments(&legacy)[0].target,
+            EnchantmentTarget::Book
+        );
+    }
+
+    #[test]
+    fn parse_predicates() {
+        let any = EnchantmentPredicate::parse("mending").unwrap();
+        assert_eq!(any.id, "minecraft:mending");
+        assert_eq!(
+            (any.target, any.min_level, any.max_level),
+            (None, None, None)
+        );
+
+        let at_least = EnchantmentPredicate::parse("item:sharpness>=5").unwrap();
+        assert_eq!(at_least.target, Some(EnchantmentTarget::Item));
+        assert_eq!((at_least.min_level, at_least.max_level), (Some(5), None));
+
+        let range = EnchantmentPredicate::parse("book:minecraft:protection=2..3").unwrap();
+        assert_eq!(range.id, "minecraft:protection");
+        assert_eq!((range.min_level, range.max_level), (Some(2), Some(3)));
+
+        let below = EnchantmentPredicate::parse("unbreaking<3").unwrap();
+        assert_eq!((below.min_level, below.max_level), (None, Some(2)));
+
+        assert!(EnchantmentPredicate::parse(">=5").is_err());
+        assert!(EnchantmentPredicate::parse("sharpness>=x").is_err());
+        assert!(EnchantmentPredicate::parse("sharpness!5").is_err());
+        assert!(EnchantmentPredicate::parse("sharpness>9223372036854775807").is_err());
+        assert!(EnchantmentPredicate::parse("sharpness<-9223372036854775808").is_err());
+        assert!(EnchantmentPredicate::parse("sharpness>4294967295").is_ok());
+    }
+
+    #[test]
+    fn predicate_matches_level_and_target() {
+        let sword =
+            from_snbt_str(r#"{components:{"minecraft:enchantments":{"minecraft:sharpness":4}}}"#)
+                .unwrap();
+        assert!(
+            EnchantmentPredicate::parse("sharpness")
+                .unwrap()
+                .matches(&sword)
+        );
+        assert!(
+            !EnchantmentPredicate::parse("sharpness>=5")
+                .unwrap()
+                .matches(&sword)
+        );
+        assert!(
+            !EnchantmentPredicate::parse("book:sharpness")
+                .unwrap()
+                .matches(&sword)
+        );
+    }
+}
@@ -0,0 +1,1131 @@
+use std::{
+    cell::OnceCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use regex::Regex;
+use simdnbt::borrow::NbtCompound;
+use valence_nbt::{Value, value::ValueRef};
+
+use crate::{
+    NumericMatching,
+    cli::{CliArgs, ItemFilter, parse_item_arg, try_parse_item_arg},
+    enchantments::EnchantmentPredicate,
+    item_paths::NestedItemPaths,
+    nbt_compound_is_subset, nbt_contains_numbers, nbt_is_subset_with,
+    nbt_path::NbtPath,
+    nbt_values_equal,
+    text_component::flatten_text_component,
+};
+
+/// A boolean combination of item predicates.
+#[derive(Debug)]
+pub enum FilterExpr {
+    Item(ItemFilter),
+    Path(PathPredicate),
+    Text(TextPredicate),
+    Enchantment(EnchantmentPredicate),
+    /// Nesting depth within container items, 0 for loose items
+    Depth {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    /// Nested (at any depth) inside an item with the ID or tag of the filter, whose NBT is
+    /// not checked
+    Inside(ItemFilter),
+    Not(Box<FilterExpr>),
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+}
+
+/// Comparison applied to the values reached by an `NbtPath`.
+#[derive(Debug)]
+pub enum PathOp {
+    Exists,
+    Missing,
+    Equals(Value),
+    NotEquals(Value),
+    Contains(String),
+    StartsWith(String),
+    Matches(Regex),
+}
+
+/// A predicate on the item NBT, e.g. `components."minecraft:custom_data".owner exists`.
+#[derive(Debug)]
+pub struct PathPredicate {
+    pub path: NbtPath,
+    pub op: PathOp,
+}
+
+impl PathPredicate {
+    /// Evaluates the predicate against the full item NBT. When the path reaches several
+    /// values (through `[]`), the predicate holds if any of them satisfies it;
+    /// `!exists` and `!=` hold only if none does.
+    pub fn matches(&self, item_nbt: &Value, numeric: NumericMatching) -> bool {
+        let values = self.path.resolve(item_nbt);
+        let equals =
+            |v: &ValueRef, expected: &Value| nbt_values_equal(&v.to_value(), expected, numeric);
+        match &self.op {
+            PathOp::Exists => !values.is_empty(),
+            PathOp::Missing => values.is_empty(),
+            PathOp::Equals(expected) => values.iter().any(|v| equals(v, expected)),
+            PathOp::NotEquals(expected) => !values.iter().any(|v| equals(v, expected)),
+            PathOp::Contains(needle) => any_string(&values, |s| s.contains(needle.as_str())),
+            PathOp::StartsWith(prefix) => any_string(&values, |s| s.starts_with(prefix.as_str())),
+            PathOp::Matches(regex) => any_string(&values, |s| regex.is_match(s)),
+        }
+    }
+}
+
+/// Applies `predicate` to string values, and to the string elements of list values.
+fn any_string(values: &[ValueRef], predicate: impl Fn(&str) -> bool) -> bool {
+    values.iter().any(|value| match value {
+        ValueRef::String(s) => predicate(s),
+        ValueRef::List(list) => list.iter().any(|element| match element {
+            ValueRef::String(s) => predicate(s),
+            _ => false,
+        }),
+        _ => false,
+    })
+}
+
+/// Which displayed text of an item a `TextPredicate` looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    /// `minecraft:custom_name`, or `tag.display.Name` before 1.20.5
+    CustomName,
+    /// Any line of `minecraft:lore`, or `tag.display.Lore` before 1.20.5
+    Lore,
+}
+
+impl TextField {
+    fn paths(self) -> [&'static [&'static str]; 2] {
+        match self {
+            TextField::CustomName => [
+                &["components", "minecraft:custom_name"],
+                &["tag", "display", "Name"],
+            ],
+            TextField::Lore => [
+                &["components", "minecraft:lore"],
+                &["tag", "display", "Lore"],
+            ],
+        }
+    }
+}
+
+/// A case-insensitive comparison on the plain text of a name or lore line.
+#[derive(Debug)]
+pub struct TextPredicate {
+    pub field: TextField,
+    /// Lowercased text to compare against
+    pub text: String,
+    /// Whether the text only has to appear somewhere instead of matching entirely
+    pub contains: bool,
+}
+
+impl TextPredicate {
+    pub fn new(field: TextField, text: &str, contains: bool) -> Self {
+        Self {
+            field,
+            text: text.to_lowercase(),
+            contains,
+        }
+    }
+
+    pub fn matches(&self, item_nbt: &Value) -> bool {
+        self.field.paths().iter().any(|path| {
+            let Some(value) = lookup(item_nbt, path) else {
+                return false;
+            };
+            match value {
+                ValueRef::List(lines) if self.field == TextField::Lore => {
+                    lines.iter().any(|line| self.matches_text(line))
+                }
+                _ => self.matches_text(value),
+            }
+        })
+    }
+
+    fn matches_text(&self, component: ValueRef) -> bool {
+        let plain = flatten_text_component(component).to_lowercase();
+        if self.contains {
+            plain.contains(&self.text)
+        } else {
+            plain.trim() == self.text.trim()
+        }
+    }
+}
+
+fn lookup<'a>(root: &'a Value, path: &[&str]) -> Option<ValueRef<'a>> {
+    path.iter()
+        .try_fold(root.as_value_ref(), |value, key| match value {
+            ValueRef::Compound(compound) => compound.get(*key).map(Value::as_value_ref),
+            _ => None,
+        })
+}
+
+/// Restricts counting to items held by matching block entities, entities or players,
+/// e.g. `minecraft:hopper`, `*shulker_box` or `chest{Lock:{}}`.
+#[derive(Debug)]
+pub struct HolderFilter {
+    /// Holder ID pattern where `*` matches any sequence of characters
+    pub pattern: String,
+    pub required_nbt: Option<Value>,
+}
+
+impl HolderFilter {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let (pattern, snbt) = match raw.find('{') {
+            Some(idx) => (raw[..idx].trim(), Some(&raw[idx..])),
+            None => (raw.trim(), None),
+        };
+        let pattern = if pattern.is_empty() {
+            "*".to_string()
+        } else if pattern.contains(':') || pattern.starts_with('*') {
+            pattern.to_string()
+        } else {
+            format!("minecraft:{pattern}")
+        };
+        let required_nbt = snbt
+            .map(|snbt| {
+                valence_nbt::snbt::from_snbt_str(snbt)
+                    .map_err(|e| format!("invalid holder NBT '{snbt}': {e}"))
+            })
+            .transpose()?;
+        Ok(Self {
+            pattern,
+            required_nbt,
+        })
+    }
+
+    pub fn matches_id(&self, id: &str) -> bool {
+        glob_match(&self.pattern, id)
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` stands for any (possibly empty) sequence.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn raw_nbt_contains(nbt: &NbtCompound, required: &Value, numeric: NumericMatching) -> bool {
+    match required {
+        Value::Compound(required) => nbt_compound_is_subset(nbt, required, numeric),
+        _ => false,
+    }
+}
+
+/// The item being tested against a `FilterExpr`.
+/// The NBT is only converted to a `valence_nbt::Value` if a predicate needs it.
+pub struct ItemContext<'a, 'tape> {
+    pub id: &'a str,
+    /// IDs of the container items this item is nested in, outermost first
+    pub parents: &'a [String],
+    raw_nbt: Option<NbtCompound<'a, 'tape>>,
+    nbt: OnceCell<Value>,
+}
+
+impl<'a, 'tape> ItemContext<'a, 'tape> {
+    pub fn new(id: &'a str, item_nbt: NbtCompound<'a, 'tape>) -> Self {
+        Self {
+            id,
+            parents: &[],
+            raw_nbt: Some(item_nbt),
+            nbt: OnceCell::new(),
+        }
+    }
+
+    /// Builds a context from already converted NBT.
+    pub fn from_value(id: &'a str, item_nbt: Value) -> Self {
+        Self {
+            id,
+            parents: &[],
+            raw_nbt: None,
+            nbt: OnceCell::from(item_nbt),
+        }
+    }
+
+    pub fn with_parents(mut self, parents: &'a [String]) -> Self {
+        self.parents = parents;
+        self
+    }
+
+    /// Whether the item NBT contains `required`. Reads the unconverted NBT unless another
+    /// predicate already needed the conversion.
+    pub fn nbt_contains(&self, required: &Value, numeric: NumericMatching) -> bool {
+        match (self.nbt.get(), &self.raw_nbt) {
+            (None, Some(raw_nbt)) => raw_nbt_contains(raw_nbt, required, numeric),
+            _ => nbt_is_subset_with(self.nbt(), required, numeric),
+        }
+    }
+
+    /// The full item NBT (`id`, `count`, `components`, ...).
+    pub fn nbt(&self) -> &Value {
+        self.nbt.get_or_init(|| {
+            self.raw_nbt
+                .as_ref()
+                .map(crate::nbt_utils::convert_simdnbt_to_valence_nbt)
+                .unwrap_or_else(|| Value::Compound(Default::default()))
+        })
+    }
+}
+
+impl FilterExpr {
+    pub fn matches(&self, item: &ItemContext, numeric: NumericMatching) -> bool {
+        match self {
+            FilterExpr::Item(filter) => {
+                filter.matches_id(item.id)
+                    && filter
+                        .required_nbt
+                        .as_ref()
+                        .is_none_or(|req| item.nbt_contains(req, numeric))
+            }
+            FilterExpr::Path(predicate) => predicate.matches(item.nbt(), numeric),
+            FilterExpr::Text(predicate) => predicate.matches(item.nbt()),
+            FilterExpr::Enchantment(predicate) => predicate.matches(item.nbt()),
+            FilterExpr::Depth { min, max } => {
+                let depth = item.parents.len();
+                min.is_none_or(|min| depth >= min) && max.is_none_or(|max| depth <= max)
+            }
+            FilterExpr::Inside(filter) => item.parents.iter().any(|id| filter.matches_id(id)),
+            FilterExpr::Not(inner) => !inner.matches(item, numeric),
+            FilterExpr::And(exprs) => exprs.iter().all(|e| e.matches(item, numeric)),
+            FilterExpr::Or(exprs) => exprs.iter().any(|e| e.matches(item, numeric)),
+        }
+    }
+
+    /// Returns `false` if no item with this ID can match, whatever its NBT, so that most
+    /// items can be rejected by ID before any NBT is read.
+    pub fn may_match_id(&self, id: &str) -> bool {
+        match self {
+            FilterExpr::Item(filter) => filter.matches_id(id),
+            FilterExpr::And(exprs) => exprs.iter().all(|e| e.may_match_id(id)),
+            FilterExpr::Or(exprs) => exprs.iter().any(|e| e.may_match_id(id)),
+            // An item rejected by an inner filter can still match its negation
+            FilterExpr::Not(_)
+            | FilterExpr::Path(_)
+            | FilterExpr::Text(_)
+            | FilterExpr::Enchantment(_)
+            | FilterExpr::Depth { .. }
+            | FilterExpr::Inside(_) => true,
+        }
+    }
+
+    /// Returns `true` if the expression compares against any numeric NBT value,
+    /// i.e. if lenient numeric matching could change its outcome.
+    pub fn has_numeric_constraints(&self) -> bool {
+        match self {
+            FilterExpr::Item(filter) => filter
+                .required_nbt
+                .as_ref()
+                .is_some_and(nbt_contains_numbers),
+            FilterExpr::Path(predicate) => match &predicate.op {
+                PathOp::Equals(value) | PathOp::NotEquals(value) => nbt_contains_numbers(value),
+                _ => false,
+            },
+            FilterExpr::Text(_)
+            | FilterExpr::Enchantment(_)
+            | FilterExpr::Depth { .. }
+            | FilterExpr::Inside(_) => false,
+            FilterExpr::Not(inner) => inner.has_numeric_constraints(),
+            FilterExpr::And(exprs) | FilterExpr::Or(exprs) => {
+                exprs.iter().any(FilterExpr::has_numeric_constraints)
+            }
+        }
+    }
+
+    /// Returns `true` if any `ItemFilter` leaf satisfies `predicate`.
+    pub fn any_item_filter(&self, predicate: &impl Fn(&ItemFilter) -> bool) -> bool {
+        match self {
+            FilterExpr::Item(filter) | FilterExpr::Inside(filter) => predicate(filter),
+            FilterExpr::Path(_)
+            | FilterExpr::Text(_)
+            | FilterExpr::Enchantment(_)
+            | FilterExpr::Depth { .. } => false,
+            FilterExpr::Not(inner) => inner.any_item_filter(predicate),
+            FilterExpr::And(exprs) | FilterExpr::Or(exprs) => {
+                exprs.iter().any(|e| e.any_item_filter(predicate))
+            }
+        }
+    }
+
+    /// Visits every `ItemFilter` leaf of the expression.
+    pub fn for_each_item_filter(&self, f: &mut impl FnMut(&ItemFilter)) {
+        match self {
+            FilterExpr::Item(filter) | FilterExpr::Inside(filter) => f(filter),
+            FilterExpr::Path(_)
+            | FilterExpr::Text(_)
+            | FilterExpr::Enchantment(_)
+            | FilterExpr::Depth { .. } => {}
+            FilterExpr::Not(inner) => inner.for_each_item_filter(f),
+            FilterExpr::And(exprs) | FilterExpr::Or(exprs) => {
+                for expr in exprs {
+                    expr.for_each_item_filter(f);
+                }
+            }
+        }
+    }
+
+    /// Visits every `ItemFilter` leaf of the expression, mutably.
+    pub fn for_each_item_filter_mut(&mut self, f: &mut impl FnMut(&mut ItemFilter)) {
+        match self {
+            FilterExpr::Item(filter) | FilterExpr::Inside(filter) => f(filter),
+            FilterExpr::Path(_)
+            | FilterExpr::Text(_)
+            | FilterExpr::Enchantment(_)
+            | FilterExpr::Depth { .. } => {}
+            FilterExpr::Not(inner) => inner.for_each_item_filter_mut(f),
+            FilterExpr::And(exprs) | FilterExpr::Or(exprs) => {
+                for expr in exprs {
+                    expr.for_each_item_filter_mut(f);
+                }
+            }
+        }
+    }
+}
+
+/// The complete item query built from the command line.
+/// A query without an expression matches every item.
+#[derive(Debug, Default)]
+pub struct ItemQuery {
+    pub expr: Option<FilterExpr>,
+    /// Items are only counted in holders matching one of these filters (all holders if empty)
+    pub holders: Vec<HolderFilter>,
+    /// Where to look for items nested inside other items
+    pub nested_paths: NestedItemPaths,
+    pub numeric: NumericMatching,
+    /// Whether strict matching should look for items only a lenient match would accept
+    track_lenient: bool,
+    /// Set once an item matched, after which the lenient re-check is pointless
+    any_match: AtomicBool,
+    lenient_only_match: AtomicBool,
+}
+
+impl ItemQuery {
+    pub fn match_all() -> Self {
+        Self::default()
+    }
+
+    /// A query matching items by `expr` only, with default settings.
+    pub fn from_expr(expr: FilterExpr) -> Self {
+        Self {
+            expr: Some(expr),
+            ..Self::default()
+        }
+    }
+
+    /// Combines `--item`, `--filter`, `--name`, `--lore-contains`, `--enchantment`, the depth
+    /// options and `--exclude` into a single expression: `(item1 or item2 ...) and filter1 and ...
+    /// and name and lore1 and ... and enchantment1 and ... and depth and (inside1 or inside2 ...)
+    /// and not (exclude1 or exclude2 ...)`.
+    pub fn from_cli_args(args: &CliArgs) -> Result<Self, String> {
+        let mut conjuncts = Vec::new();
+
+        if !args.all && !args.items.is_empty() {
+            conjuncts.push(FilterExpr::Or(
+                args.items
+                    .iter()
+                    .map(|raw| FilterExpr::Item(parse_item_arg(raw)))
+                    .collect(),
+            ));
+        }
+
+        for raw_filter in &args.filters {
+            conjuncts.push(parse_filter_expr(raw_filter)?);
+        }
+
+        if let Some(name) = &args.name {
+            conjuncts.push(FilterExpr::Text(TextPredicate::new(
+                TextField::CustomName,
+                name,
+                false,
+            )));
+        }
+        for lore in &args.lore_contains {
+            conjuncts.push(FilterExpr::Text(TextPredicate::new(
+                TextField::Lore,
+                lore,
+                true,
+            )));
+        }
+
+        for spec in &args.enchantments {
+            conjuncts.push(FilterExpr::Enchantment(
+                EnchantmentPredicate::parse(spec)
+                    .map_err(|e| format!("invalid --enchantment '{spec}': {e}"))?,
+            ));
+        }
+
+        if args.min_depth.is_some() || args.max_depth.is_some() {
+            conjuncts.push(FilterExpr::Depth {
+                min: args.min_depth,
+                max: args.max_depth,
+            });
+        }
+        if !args.inside.is_empty() {
+            conjuncts.push(FilterExpr::Or(
+                args.inside
+                    .iter()
+                    .map(|raw| {
+                        let filter = try_parse_item_arg(raw)
+                            .map_err(|e| format!("invalid --inside '{raw}': {e}"))?;
+                        // Only the IDs of the containers are known when matching
+                        if filter.required_nbt.is_some() {
+                            return Err(format!(
+                                "invalid --inside '{raw}': only item IDs and tags are supported, not NBT"
+                            ));
+                        }
+                        Ok(FilterExpr::Inside(filter))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ));
+        }
+
+        if !args.excludes.is_empty() {
+            conjuncts.push(FilterExpr::Not(Box::new(FilterExpr::Or(
+                args.excludes
+                    .iter()
+                    .map(|raw| {
+                        try_parse_item_arg(raw)
+                            .map(FilterExpr::Item)
+                            .map_err(|e| format!("invalid --exclude '{raw}': {e}"))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ))));
+        }
+
+        let expr = match conjuncts.len() {
+            0 => None,
+            1 => conjuncts.pop(),
+            _ => Some(FilterExpr::And(conjuncts)),
+        };
+        let numeric = if args.lenient_numbers {
+            NumericMatching::Lenient
+        } else {
+            NumericMatching::Strict
+        };
+        let track_lenient = numeric == NumericMatching::Strict
+            && expr
+                .as_ref()
+                .is_some_and(FilterExpr::has_numeric_constraints);
+        let holders = args
+            .in_containers
+            .iter()
+            .map(|raw| {
+                HolderFilter::parse(raw).map_err(|e| format!("invalid --in-container '{raw}': {e}"))
+            })
+            .collect::<Result<_, _>>()?;
+        let mut nested_paths = if args.no_default_nested_paths {
+            NestedItemPaths::new()
+        } else {
+            NestedItemPaths::vanilla()
+        };
+        for path in &args.nested_item_paths {
+            nested_paths
+                .add(path)
+                .map_err(|e| format!("invalid --nested-item-path '{path}': {e}"))?;
+        }
+        Ok(Self {
+            expr,
+            holders,
+            nested_paths,
+            numeric,
+            track_lenient,
+            any_match: AtomicBool::new(false),
+            lenient_only_match: AtomicBool::new(false),
+        })
+    }
+
+    pub fn is_match_all(&self) -> bool {
+        self.expr.is_none() && self.holders.is_empty()
+    }
+
+    /// Whether items held by the block entity, entity or player `id` should be considered at all.
+    pub fn matches_holder(&self, id: &str, holder_nbt: NbtCompound) -> bool {
+        if self.holders.is_empty() {
+            return true;
+        }
+        self.holders.iter().any(|filter| {
+            filter.matches_id(id)
+                && filter
+                    .required_nbt
+                    .as_ref()
+                    .is_none_or(|req| raw_nbt_contains(&holder_nbt, req, self.numeric))
+        })
+    }
+
+    pub fn matches(&self, item: &ItemContext) -> bool {
+        let Some(expr) = &self.expr else {
+            return true;
+        };
+        if !expr.may_match_id(item.id) {
+            return false;
+        }
+        let matched = expr.matches(item, self.numeric);
+        if !self.track_lenient {
+            return matched;
+        }
+        // Matching twice is only worth it until the scan is known to find something, with or
+        // without --lenient-numbers
+        if matched {
+            self.any_match.store(true, Ordering::Relaxed);
+        } else if !self.any_match.load(Ordering::Relaxed)
+            && !self.lenient_only_match.load(Ordering::Relaxed)
+            && expr.matches(item, NumericMatching::Lenient)
+        {
+            self.lenient_only_match.store(true, Ordering::Relaxed);
+        }
+        matched
+    }
+
+    /// Whether an item was rejected by strict numeric matching that `--lenient-numbers` would
+    /// accept. Only tracked while no item matched.
+    pub fn has_lenient_only_match(&self) -> bool {
+        self.lenient_only_match.load(Ordering::Relaxed)
+    }
+
+    pub fn uses_tags(&self) -> bool {
+        self.expr
+            .as_ref()
+            .is_some_and(|expr| expr.any_item_filter(&|filter| filter.tag.is_some()))
+    }
+
+    pub fn for_each_item_filter(&self, mut f: impl FnMut(&ItemFilter)) {
+        if let Some(expr) = &self.expr {
+            expr.for_each_item_filter(&mut f);
+        }
+    }
+
+    pub fn for_each_item_filter_mut(&mut self, mut f: impl FnMut(&mut ItemFilter)) {
+        if let Some(expr) = &mut self.expr {
+            expr.for_each_item_filter_mut(&mut f);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Atom(String),
+}
+
+/// Splits an expression into tokens. Atoms are item specs (`ID{snbt}`, `#tag`), NBT paths,
+/// path operators and their operands; braces, brackets and quoted strings inside an atom
+/// may contain spaces and parentheses.
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '!' if !input[start + 1..].starts_with('=') => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next_if(|&(_, next)| next == c).is_none() {
+                    return Err(format!("expected '{c}{c}' at position {start}"));
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            _ => {
+                let mut depth = 0usize;
+                let mut quote = None;
+                let mut escaped = false;
+                let mut end = input.len();
+                for (i, ch) in chars.by_ref() {
+                    if let Some(q) = quote {
+                        if escaped {
+                            escaped = false;
+                        } else if ch == '\\' {
+                            escaped = true;
+                        } else if ch == q {
+                            quote = None;
+                        }
+                    } else {
+                        match ch {
+                            '"' | '\'' => quote = Some(ch),
+                            '{' | '[' => depth += 1,
+                            '}' | ']' => depth = depth.saturating_sub(1),
+                            _ => {}
+                        }
+                    }
+                    let next_ends_atom = quote.is_none()
+                        && depth == 0
+                        && input[i + ch.len_utf8()..]
+                            .chars()
+                            .next()
+                            .is_none_or(|next| next.is_whitespace() || next == '(' || next == ')');
+                    if next_ends_atom {
+                        end = i + ch.len_utf8();
+                        break;
+                    }
+                }
+                if quote.is_some() || depth > 0 {
+                    return Err(format!(
+                        "unterminated item spec starting at position {start}"
+                    ));
+                }
+                let word = &input[start..end];
+                tokens.push(match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Atom(word.to_string()),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent parser. Precedence, from lowest to highest: `or`, `and`, `not`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, String> {
+        let mut operands = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            operands.push(self.parse_and()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.remove(0)
+        } else {
+            FilterExpr::Or(operands)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, String> {
+        let mut operands = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            operands.push(self.parse_unary()?);
+        }
+        Ok(if operands.len() == 1 {
+            operands.remove(0)
+        } else {
+            FilterExpr::And(operands)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(FilterExpr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err("expected ')'".to_string()),
+                }
+            }
+            Some(Token::Atom(atom)) => match self.parse_path_op()? {
+                Some(op) => Ok(FilterExpr::Path(PathPredicate {
+                    path: NbtPath::parse(&atom)?,
+                    op,
+                })),
+                None => Ok(FilterExpr::Item(try_parse_item_arg(&atom)?)),
+            },
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+
+    /// Parses the operator following an atom, if any, which turns the atom into an NBT path.
+    fn parse_path_op(&mut self) -> Result<Option<PathOp>, String> {
+        let is_exists = |token: Option<&Token>| matches!(token, Some(Token::Atom(word)) if word.eq_ignore_ascii_case("exists"));
+
+        if is_exists(self.peek()) {
+            self.next();
+            return Ok(Some(PathOp::Exists));
+        }
+        if self.peek() == Some(&Token::Not) && is_exists(self.tokens.get(self.pos + 1)) {
+            self.pos += 2;
+            return Ok(Some(PathOp::Missing));
+        }
+
+        let Some(Token::Atom(word)) = self.peek() else {
+            return Ok(None);
+        };
+        let op_name = word.to_ascii_lowercase();
+        if !matches!(
+            op_name.as_str(),
+            "==" | "!=" | "contains" | "startswith" | "matches" | "=~"
+        ) {
+            return Ok(None);
+        }
+        self.next();
+
+        let Some(Token::Atom(operand)) = self.next() else {
+            return Err(format!("expected a value after '{op_name}'"));
+        };
+        let op = match op_name.as_str() {
+            "==" => PathOp::Equals(parse_snbt_value(&operand)?),
+            "!=" => PathOp::NotEquals(parse_snbt_value(&operand)?),
+            "contains" => PathOp::Contains(parse_string_operand(&operand)?),
+            "startswith" => PathOp::StartsWith(parse_string_operand(&operand)?),
+            _ => {
+                let pattern = parse_string_operand(&operand)?;
+                PathOp::Matches(
+                    Regex::new(&pattern).map_err(|e| format!("invalid regex '{pattern}': {e}"))?,
+                )
+            }
+        };
+        Ok(Some(op))
+    }
+}
+
+fn parse_snbt_value(operand: &str) -> Result<Value, String> {
+    valence_nbt::snbt::from_snbt_str(operand)
+        .map_err(|e| format!("invalid SNBT value '{operand}': {e}"))
+}
+
+/// String operands may be quoted SNBT strings or bare words.
+fn parse_string_operand(operand: &str) -> Result<String, String> {
+    if operand.starts_with('"') || operand.starts_with('\'') {
+        match parse_snbt_value(operand)? {
+            Value::String(s) => Ok(s),
+            _ => Err(format!("expected a string, got '{operand}'")),
+        }
+    } else {
+        Ok(operand.to_string())
+    }
+}
+
+/// Parses a filter expression such as
+/// `minecraft:enchanted_book and not minecraft:enchanted_book{components:{...}}`.
+pub fn parse_filter_expr(input: &str) -> Result<FilterExpr, String> {
+    let tokens = tokenize(input).map_err(|e| format!("invalid filter '{input}': {e}"))?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser
+        .parse_or()
+        .map_err(|e| format!("invalid filter '{input}': {e}"))?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("invalid filter '{input}': unexpected {token:?}"));
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_utils::snbt_to_binary;
+    use valence_nbt::snbt::from_snbt_str;
+
+    fn item(id: &str, snbt: &str) -> (String, Value) {
+        (id.to_string(), from_snbt_str(snbt).unwrap())
+    }
+
+    fn eval(expr: &str, (id, nbt): &(String, Value)) -> bool {
+        let expr = parse_filter_expr(expr).unwrap();
+        expr.matches(
+            &ItemContext::from_value(id, nbt.clone()),
+            NumericMatching::Strict,
+        )
+    }
+
+    #[test]
+    fn tokenize_keeps_snbt_with_spaces_and_parens_together() {
+        let tokens = tokenize(r#"(stone{a:"x (y) z"} or dirt) and not #logs"#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Atom(r#"stone{a:"x (y) z"}"#.to_string()),
+                Token::Or,
+                Token::Atom("dirt".to_string()),
+                Token::RParen,
+                Token::And,
+                Token::Not,
+                Token::Atom("#logs".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn precedence_not_and_or() {
+        let diamond = item("minecraft:diamond", "{}");
+        assert!(eval("dirt or diamond and not stone", &diamond));
+        assert!(!eval("(dirt or diamond) and not diamond", &diamond));
+        assert!(eval("!dirt && (diamond || stone)", &diamond));
+    }
+
+    #[test]
+    fn exclude_by_nbt() {
+        let mending = item(
+            "minecraft:enchanted_book",
+            r#"{components:{"minecraft:stored_enchantments":{"minecraft:mending":1}}}"#,
+        );
+        let unbreaking = item(
+            "minecraft:enchanted_book",
+            r#"{components:{"minecraft:stored_enchantments":{"minecraft:unbreaking":3}}}"#,
+        );
+        let expr = r#"enchanted_book and not {components:{"minecraft:stored_enchantments":{"minecraft:mending":1}}}"#;
+        assert!(!eval(expr, &mending));
+        assert!(eval(expr, &unbreaking));
+    }
+
+    #[test]
+    fn path_predicates() {
+        let book = item(
+            "minecraft:written_book",
+            r#"{components:{"minecraft:custom_data":{owner:"Alex",level:3},"minecraft:lore":["Found in the Nether","Rare"]}}"#,
+        );
+        assert!(eval(r#"components."minecraft:custom_data" exists"#, &book));
+        assert!(eval(
+            r#"components."minecraft:enchantments" !exists"#,
+            &book
+        ));
+        assert!(eval(
+            r#"components."minecraft:enchantments" not exists"#,
+            &book
+        ));
+        assert!(!eval(
+            r#"components."minecraft:custom_data".owner !exists"#,
+            &book
+        ));
+        assert!(eval(
+            r#"components."minecraft:custom_data".level == 3"#,
+            &book
+        ));
+        assert!(!eval(
+            r#"components."minecraft:custom_data".level == 3b"#,
+            &book
+        ));
+        assert!(eval(
+            r#"components."minecraft:custom_data".owner != "Steve""#,
+            &book
+        ));
+        assert!(eval(
+            r#"components."minecraft:lore" contains "Nether""#,
+            &book
+        ));
+        assert!(eval(
+            r#"components."minecraft:lore"[1] startswith Ra"#,
+            &book
+        ));
+        assert!(eval(
+            r#"components."minecraft:custom_data".owner matches "^A.e""#,
+            &book
+        ));
+        assert!(eval(
+            r#"written_book and (components."minecraft:custom_data".owner =~ "x" or components."minecraft:lore"[] == "Rare")"#,
+            &book
+        ));
+    }
+
+    #[test]
+    fn strict_query_notices_lenient_only_matches() {
+        let book = item(
+            "minecraft:enchanted_book",
+            r#"{components:{"minecraft:stored_enchantments":{"minecraft:mending":1}}}"#,
+        );
+        let strict = ItemQuery {
+            expr: Some(parse_filter_expr(
+                r#"enchanted_book{components:{"minecraft:stored_enchantments":{"minecraft:mending":1b}}}"#,
+            ).unwrap()),
+            track_lenient: true,
+            ..Default::default()
+        };
+        let context = ItemContext::from_value(&book.0, book.1.clone());
+        assert!(!strict.matches(&context));
+        assert!(strict.has_lenient_only_match());
+
+        // Once an item matched, rejected items are no longer checked again
+        let after_match = ItemQuery {
+            expr: strict.expr,
+            track_lenient: true,
+            ..Default::default()
+        };
+        let exact = item(
+            "minecraft:enchanted_book",
+            r#"{components:{"minecraft:stored_enchantments":{"minecraft:mending":1b}}}"#,
+        );
+        assert!(after_match.matches(&ItemContext::from_value(&exact.0, exact.1.clone())));
+        assert!(!after_match.matches(&context));
+        assert!(!after_match.has_lenient_only_match());
+
+        let lenient = ItemQuery {
+            expr: Some(
+                parse_filter_expr(
+                    r#"components."minecraft:stored_enchantments"."minecraft:mending" == 1L"#,
+                )
+                .unwrap(),
+            ),
+            numeric: NumericMatching::Lenient,
+            ..Default::default()
+        };
+        assert!(lenient.matches(&context));
+        assert!(!lenient.has_lenient_only_match());
+    }
+
+    #[test]
+    fn text_predicates_use_plain_text() {
+        let sword = item(
+            "minecraft:netherite_sword",
+            r#"{components:{"minecraft:custom_name":{text:"Excalibur",color:"gold",extra:[" of Doom"]},"minecraft:lore":[{text:"Forged in ",extra:[{text:"the Nether",color:"red"}]},{text:"Legendary"}]}}"#,
+        );
+        let context = ItemContext::from_value(&sword.0, sword.1.clone());
+        let name = |text| FilterExpr::Text(TextPredicate::new(TextField::CustomName, text, false));
+        let lore = |text| FilterExpr::Text(TextPredicate::new(TextField::Lore, text, true));
+
+        assert!(name("excalibur OF doom").matches(&context, NumericMatching::Strict));
+        assert!(!name("Excalibur").matches(&context, NumericMatching::Strict));
+        assert!(lore("in the nether").matches(&context, NumericMatching::Strict));
+        assert!(!lore("Nether Legendary").matches(&context, NumericMatching::Strict));
+
+        let legacy = item(
+            "minecraft:diamond_pickaxe",
+            r#"{tag:{display:{Name:"{\"text\":\"Old Pick\"}",Lore:["{\"text\":\"Mined a lot\",\"italic\":false}"]}}}"#,
+        );
+        let context = ItemContext::from_value(&legacy.0, legacy.1.clone());
+        assert!(name("old pick").matches(&context, NumericMatching::Strict));
+        assert!(lore("MINED").matches(&context, NumericMatching::Strict));
+    }
+
+    #[test]
+    fn depth_and_inside() {
+        let diamond = item("minecraft:diamond", "{}");
+        let loose = ItemContext::from_value(&diamond.0, diamond.1.clone());
+        let parents = [
+            "minecraft:shulker_box".to_string(),
+            "minecraft:bundle".to_string(),
+        ];
+        let nested = ItemContext::from_value(&diamond.0, diamond.1.clone()).with_parents(&parents);
+
+        let loose_only = FilterExpr::Depth {
+            min: None,
+            max: Some(0),
+        };
+        assert!(loose_only.matches(&loose, NumericMatching::Strict));
+        assert!(!loose_only.matches(&nested, NumericMatching::Strict));
+
+        let deep = FilterExpr::Depth {
+            min: Some(2),
+            max: None,
+        };
+        assert!(deep.matches(&nested, NumericMatching::Strict));
+
+        let in_shulker = FilterExpr::Inside(parse_item_arg("shulker_box"));
+        assert!(in_shulker.matches(&nested, NumericMatching::Strict));
+        assert!(!in_shulker.matches(&loose, NumericMatching::Strict));
+    }
+
+    #[test]
+    fn holder_filters() {
+        assert!(glob_match(
+            "minecraft:*shulker_box",
+            "minecraft:red_shulker_box"
+        ));
+        assert!(glob_match("*chest*", "minecraft:chest_minecart"));
+        assert!(glob_match("a*b*c", "abbc"));
+        assert!(!glob_match("a*b*c", "acb"));
+        assert!(!glob_match("minecraft:chest", "minecraft:chest_minecart"));
+
+        let hopper = HolderFilter::parse("hopper").unwrap();
+        assert_eq!(hopper.pattern, "minecraft:hopper");
+        assert!(hopper.matches_id("minecraft:hopper"));
+        assert!(!hopper.matches_id("minecraft:hopper_minecart"));
+
+        let locked = ItemQuery {
+            holders: vec![HolderFilter::parse(r#"*chest{Lock:{}}"#).unwrap()],
+            ..Default::default()
+        };
+        let holder_matches = |snbt: &str| {
+            let bytes = snbt_to_binary(snbt);
+            let nbt = simdnbt::borrow::read(&mut std::io::Cursor::new(&bytes[..]))
+                .unwrap()
+                .unwrap();
+            locked.matches_holder("minecraft:chest", nbt.as_compound())
+        };
+        assert!(holder_matches(
+            r#"{id:"minecraft:chest",Lock:{components:{}}}"#
+        ));
+        assert!(!holder_matches(r#"{id:"minecraft:chest"}"#));
+
+        assert!(HolderFilter::parse("chest{Lock:").is_err());
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        assert!(parse_filter_expr("").is_err());
+        assert!(parse_filter_expr("(diamond").is_err());
+        assert!(parse_filter_expr("diamond and").is_err());
+        assert!(parse_filter_expr("diamond dirt").is_err());
+        assert!(parse_filter_expr("stone{a:1").is_err());
+        assert!(parse_filter_expr("not enchanted_book{bad:}").is_err());
+        assert!(parse_filter_expr("diamond & dirt").is_err());
+        assert!(parse_filter_expr("components.x ==").is_err());
+        assert!(parse_filter_expr("components.x matches \"(\"").is_err());
+        assert!(parse_filter_expr("components. exists").is_err());
+    }
+
+    #[test]
+    fn rejects_items_by_id_before_reading_nbt() {
+        let expr = parse_filter_expr("(diamond or iron_sword) and not emerald").unwrap();
+        assert!(expr.may_match_id("minecraft:diamond"));
+        assert!(expr.may_match_id("minecraft:iron_sword"));
+        assert!(!expr.may_match_id("minecraft:stone"));
+        assert!(
+            parse_filter_expr("not stone")
+                .unwrap()
+                .may_match_id("minecraft:stone")
+        );
+    }
+
+    #[test]
+    fn inside_rejects_nbt() {
+        use clap::Parser;
+        let args = |inside: &str| {
+            CliArgs::try_parse_from(["nbt-sniffer", "-w", "w", "--all", "--inside", inside])
+                .unwrap()
+        };
+        assert!(ItemQuery::from_cli_args(&args("shulker_box")).is_ok());
+        assert!(ItemQuery::from_cli_args(&args("shulker_box{components:{}}")).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::cli::HeatmapResolution;
+
+/// Matched item counts keyed by chunk coordinates.
+pub type ChunkCounts = HashMap<(i32, i32), u64>;
+
+const CHUNKS_PER_REGION: i32 = 32;
+const BLOCKS_PER_CHUNK: i32 = 16;
+
+/// Largest side of the map area, in pixels, before cells shrink to a single pixel. Beyond
+/// it, several chunks or regions are merged into each cell.
+const TARGET_MAP_SIZE: usize = 1024;
+const MAX_CELL_PIXELS: usize = 16;
+const TEXT_SCALE: usize = 2;
+const GLYPH_ADVANCE: usize = 4 * TEXT_SCALE;
+const GLYPH_HEIGHT: usize = 5 * TEXT_SCALE;
+const MIN_TICK_SPACING: usize = 48;
+const MARGIN: usize = 8;
+const LEGEND_BAR_WIDTH: usize = 256;
+const LEGEND_BAR_HEIGHT: usize = 12;
+
+type Rgb = [u8; 3];
+
+const BACKGROUND: Rgb = [255, 255, 255];
+const TEXT: Rgb = [32, 32, 32];
+const EMPTY_CELL: Rgb = [28, 28, 36];
+/// Color stops from the fewest to the most items.
+const COLOR_STOPS: [Rgb; 4] = [
+    [60, 20, 110],
+    [200, 40, 60],
+    [250, 160, 30],
+    [255, 255, 190],
+];
+
+/// Reads the region coordinates from a region file name such as `r.-1.2.mca`.
+pub fn region_coordinates(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some((x, z))
+}
+
+impl HeatmapResolution {
+    fn chunks_per_cell(self) -> i32 {
+        match self {
+            HeatmapResolution::Chunk => 1,
+            HeatmapResolution::Region => CHUNKS_PER_REGION,
+        }
+    }
+}
+
+/// Writes `<dir>/<dimension>.png` for every dimension with matched items, returning the
+/// paths written.
+pub fn write_heatmaps(
+    dir: &Path,
+    chunk_counts: &HashMap<String, ChunkCounts>,
+    resolution: HeatmapResolution,
+) -> Result<Vec<PathBuf>, String> {
+    std::fs::create_dir_all(dir).map_err(|e| {
+        format!(
+            "failed to create heatmap directory '{}': {e}",
+            dir.display()
+        )
+    })?;
+
+    let mut dimensions: Vec<_> = chunk_counts.keys().collect();
+    dimensions.sort();
+    let mut written = Vec::new();
+    for dimension in dimensions {
+        let chunks = &chunk_counts[dimension];
+        let mut chunks_per_cell = resolution.chunks_per_cell();
+        let mut cells = group_cells(chunks, chunks_per_cell);
+        if cells.is_empty() {
+            continue;
+        }
+        let merged = cell_merge_factor(&cells);
+        if merged > 1 {
+            chunks_per_cell *= merged;
+            cells = group_cells(chunks, chunks_per_cell);
+        }
+        let canvas = render_heatmap(&cells, resolution, merged);
+        let file_name: String = dimension
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = dir.join(format!("{file_name}.png"));
+        canvas
+            .save_png(&path)
+            .map_err(|e| format!("failed to write heatmap '{}': {e}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Sums chunk counts into cells of `chunks_per_cell` chunks per side.
+fn group_cells(chunk_counts: &ChunkCounts, chunks_per_cell: i32) -> ChunkCounts {
+    let mut cells = ChunkCounts::new();
+    for (&(x, z), &count) in chunk_counts {
+        if count > 0 {
+            let cell = (x.div_euclid(chunks_per_cell), z.div_euclid(chunks_per_cell));
+            *cells.entry(cell).or_default() += count;
+        }
+    }
+    cells
+}
+
+/// Number of cells per side to merge into one, so that the map spans at most about
+/// `TARGET_MAP_SIZE` cells.
+fn cell_merge_factor(cells: &ChunkCounts) -> i32 {
+    let span = |coordinate: fn(&(i32, i32)) -> i32| {
+        let min = cells.keys().map(coordinate).min().unwrap_or(0);
+        let max = cells.keys().map(coordinate).max().unwrap_or(0);
+        (max as i64 - min as i64 + 1) as usize
+    };
+    let span = span(|&(x, _)| x).max(span(|&(_, z)| z));
+    span.div_ceil(TARGET_MAP_SIZE) as i32
+}
+
+/// Maps `count` to a color, on a logarithmic scale up to `max`.
+fn count_color(count: u64, max: u64) -> Rgb {
+    let t = if max <= 1 {
+        1.0
+    } else {
+        (count as f64).ln() / (max as f64).ln()
+    };
+    gradient_color(t.clamp(0.0, 1.0))
+}
+
+fn gradient_color(t: f64) -> Rgb {
+    let segments = (COLOR_STOPS.len() - 1) as f64;
+    let position = t * segments;
+    let index = (position.floor() as usize).min(COLOR_STOPS.len() - 2);
+    let local = position - index as f64;
+    let (from, to) = (COLOR_STOPS[index], COLOR_STOPS[index + 1]);
+    [0, 1, 2].map(|i| (from[i] as f64 + (to[i] as f64 - from[i] as f64) * local).round() as u8)
+}
+
+/// Smallest of 1, 2, 5, 10, 20, 50... cells that keeps tick labels `min_pixels` apart.
+fn tick_step(cell_pixels: usize, min_pixels: usize) -> i32 {
+    let mut step = 1;
+    loop {
+        for factor in [1, 2, 5] {
+            if (step * factor) as usize * cell_pixels >= min_pixels {
+                return step * factor;
+            }
+        }
+        step *= 10;
+    }
+}
+
+/// Draws `cells`, each covering `merged` chunks or regions per side.
+fn render_heatmap(cells: &ChunkCounts, resolution: HeatmapResolution, merged: i32) -> Canvas {
+    let min_x = cells.keys().map(|&(x, _)| x).min().unwrap_or(0);
+    let max_x = cells.keys().map(|&(x, _)| x).max().unwrap_or(0);
+    let min_z = cells.keys().map(|&(_, z)| z).min().unwrap_or(0);
+    let max_z = cells.keys().map(|&(_, z)| z).max().unwrap_or(0);
+    let max_count = cells.values().copied().max().unwrap_or(1);
+
+    let columns = (max_x - min_x + 1) as usize;
+    let rows = (max_z - min_z + 1) as usize;
+    let cell_pixels = (TARGET_MAP_SIZE / columns.max(rows)).clamp(1, MAX_CELL_PIXELS);
+    let blocks_per_cell = resolution.chunks_per_cell() * merged * BLOCKS_PER_CHUNK;
+    let block = |cell: i32| cell * blocks_per_cell;
+
+    // Room for the widest Z label on the left, X labels above and the color scale below
+    let label_width = |cell: i32| block(cell).to_string().len() * GLYPH_ADVANCE;
+    let left = MARGIN
+        + label_width(min_z)
+            .max(label_width(max_z))
+            .max(GLYPH_ADVANCE)
+        + MARGIN;
+    let top = MARGIN + GLYPH_HEIGHT + MARGIN;
+    let map_width = columns * cell_pixels;
+    let map_height = rows * cell_pixels;
+    let legend_top = top + map_height + MARGIN * 2;
+
+    // Block coordinates of the north-west corner of every `step`-th cell
+    let step = tick_step(cell_pixels, MIN_TICK_SPACING);
+    let x_ticks: Vec<i32> = (min_x..=max_x)
+        .filter(|x| x.rem_euclid(step) == 0)
+        .collect();
+    let x_labels_end = x_ticks
+        .last()
+        .map_or(0, |&x| (x - min_x) as usize * cell_pixels + label_width(x));
+    let x_axis_label = left + map_width.max(x_labels_end) + MARGIN;
+    let width = (x_axis_label + GLYPH_ADVANCE + MARGIN).max(left + LEGEND_BAR_WIDTH + MARGIN * 12);
+    let height = legend_top + LEGEND_BAR_HEIGHT + MARGIN + GLYPH_HEIGHT * 2 + MARGIN * 2;
+
+    let mut canvas = Canvas::new(width, height, BACKGROUND);
+    canvas.fill_rect(left, top, map_width, map_height, EMPTY_CELL);
+    for (&(x, z), &count) in cells {
+        let px = left + (x - min_x) as usize * cell_pixels;
+        let py = top + (z - min_z) as usize * cell_pixels;
+        canvas.fill_rect(
+            px,
+            py,
+            cell_pixels,
+            cell_pixels,
+            count_color(count, max_count),
+        );
+    }
+
+    for x in x_ticks {
+        let px = left + (x - min_x) as usize * cell_pixels;
+        canvas.fill_rect(px, top - MARGIN / 2, 1, MARGIN / 2, TEXT);
+        canvas.draw_text(px, MARGIN, &block(x).to_string(), TEXT);
+    }
+    for z in (min_z..=max_z).filter(|z| z.rem_euclid(step) == 0) {
+        let py = top + (z - min_z) as usize * cell_pixels;
+        canvas.fill_rect(left - MARGIN / 2, py, MARGIN / 2, 1, TEXT);
+        canvas.draw_text(MARGIN, py, &block(z).to_string(), TEXT);
+    }
+    canvas.draw_text(x_axis_label, MARGIN, "X", TEXT);
+    canvas.draw_text(MARGIN, top + map_height + MARGIN, "Z", TEXT);
+
+    // Color scale, from 1 to the highest count
+    for i in 0..LEGEND_BAR_WIDTH {
+        let t = i as f64 / (LEGEND_BAR_WIDTH - 1) as f64;
+        canvas.fill_rect(
+            left + i,
+            legend_top,
+            1,
+            LEGEND_BAR_HEIGHT,
+            gradient_color(t),
+        );
+    }
+    let labels_top = legend_top + LEGEND_BAR_HEIGHT + MARGIN;
+    canvas.draw_text(left, labels_top, "1", TEXT);
+    let max_label = max_count.to_string();
+    canvas.draw_text(
+        left + LEGEND_BAR_WIDTH - max_label.len() * GLYPH_ADVANCE,
+        labels_top,
+        &max_label,
+        TEXT,
+    );
+    let area = match resolution {
+        HeatmapResolution::Chunk => "CHUNK",
+        HeatmapResolution::Region => "REGION",
+    };
+    let unit = if merged > 1 {
+        format!("ITEMS PER {merged}X{merged} {area}S")
+    } else {
+        format!("ITEMS PER {area}")
+    };
+    canvas.draw_text(left, labels_top + GLYPH_HEIGHT + MARGIN, &unit, TEXT);
+
+    canvas
+}
+
+/// Rows of a 3x5 glyph, the highest of the 3 low bits being the leftmost pixel.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' | 'O' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' | 'S' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        'C' => [0b111, 0b100, 0b100, 0b100, 0b111],
+        'E' => [0b111, 0b100, 0b111, 0b100, 0b111],
+        'G' => [0b111, 0b100, 0b101, 0b101, 0b111],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'P' => [0b111, 0b101, 0b111, 0b100, 0b100],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        _ => [0; 5],
+    }
+}
+
+/// An RGB image being drawn.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, color: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: color.repeat(width * height),
+        }
+    }
+
+    #[cfg(test)]
+    fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let i = (y * self.width + x) * 3;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+    }
+
+    /// Fills a rectangle, clipped to the canvas.
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                let i = (py * self.width + px) * 3;
+                self.pixels[i..i + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn draw_text(&mut self, x: usize, y: usize, text: &str, color: Rgb) {
+        for (index, c) in text.chars().enumerate() {
+            let glyph_x = x + index * GLYPH_ADVANCE;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        self.fill_rect(
+                            glyph_x + column * TEXT_SCALE,
+                            y + row * TEXT_SCALE,
+                            TEXT_SCALE,
+                            TEXT_SCALE,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+        let file = File::create(path)?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_region_file_names() {
+        assert_eq!(
+            region_coordinates(Path::new("world/region/r.-1.2.mca")),
+            Some((-1, 2))
+        );
+        assert_eq!(region_coordinates(Path::new("r.0.0.mcc")), None);
+        assert_eq!(region_coordinates(Path::new("r.a.0.mca")), None);
+    }
+
+    #[test]
+    fn groups_chunks_into_regions() {
+        let chunks = ChunkCounts::from([((-1, 0), 3), ((-32, 31), 2), ((32, 0), 5), ((0, 0), 0)]);
+        assert_eq!(
+            group_cells(&chunks, CHUNKS_PER_REGION),
+            ChunkCounts::from([((-1, 0), 5), ((1, 0), 5)])
+        );
+    }
+
+    #[test]
+    fn renders_cells_with_scaled_colors() {
+        let cells = ChunkCounts::from([((0, 0), 1), ((2, 1), 100)]);
+        let canvas = render_heatmap(&cells, HeatmapResolution::Chunk, 1);
+
+        let cell_pixels = MAX_CELL_PIXELS;
+        let left = MARGIN + 2 * GLYPH_ADVANCE + MARGIN;
+        let top = MARGIN + GLYPH_HEIGHT + MARGIN;
+        let center = |x: usize, z: usize| {
+            canvas.pixel(
+                left + x * cell_pixels + cell_pixels / 2,
+                top + z * cell_pixels + cell_pixels / 2,
+            )
+        };
+        assert_eq!(center(0, 0), COLOR_STOPS[0]);
+        assert_eq!(center(2, 1), COLOR_STOPS[3]);
+        assert_eq!(center(1, 0), EMPTY_CELL);
+    }
+
+    #[test]
+    fn merges_cells_of_distant_chunks() {
+        let chunks = ChunkCounts::from([((-62_500, 0), 1), ((62_500, 0), 1)]);
+        let merged = cell_merge_factor(&chunks);
+        assert_eq!(merged, 123);
+
+        let cells = group_cells(&chunks, merged);
+        let canvas = render_heatmap(&cells, HeatmapResolution::Chunk, merged);
+        assert!(canvas.width < TARGET_MAP_SIZE * 2);
+        assert_eq!(cell_merge_factor(&ChunkCounts::from([((0, 0), 1)])), 1);
+    }
+
+    #[test]
+    fn tick_steps_are_round_numbers() {
+        assert_eq!(tick_step(16, 48), 5);
+        assert_eq!(tick_step(1, 48), 50);
+        assert_eq!(tick_step(64, 48), 1);
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::File,
+    hash::{DefaultHasher, Hasher},
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::UNIX_EPOCH,
+};
+
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{
+    civil_date,
+    counter::{Counter, ItemComponents},
+};
+
+/// How a backup is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupKind {
+    Directory,
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl BackupKind {
+    fn of(path: &Path) -> Option<Self> {
+        if path.is_dir() {
+            return Some(BackupKind::Directory);
+        }
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(BackupKind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(BackupKind::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(BackupKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// A world backup, as a directory or an archive.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub name: String,
+    pub kind: BackupKind,
+    /// Seconds since the Unix epoch at which the backup was last modified
+    pub timestamp: u64,
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Lists the directories and archives in `dir`, oldest first. The timestamp of a
+/// directory is that of its `level.dat`, which changes on every save unlike the directory.
+pub fn list_backups(dir: &Path) -> Result<Vec<Backup>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("failed to read backup folder '{}': {e}", dir.display()))?;
+    let mut backups = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(kind) = BackupKind::of(&path) else {
+            continue;
+        };
+        let level_dat = path.join("level.dat");
+        let timestamp = if kind == BackupKind::Directory && level_dat.is_file() {
+            modified_secs(&level_dat)
+        } else {
+            modified_secs(&path)
+        };
+        backups.push(Backup {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path,
+            kind,
+            timestamp: timestamp.unwrap_or_default(),
+        });
+    }
+    backups.sort_by(|a, b| {
+        a.timestamp
+            .cmp(&b.timestamp)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(backups)
+}
+
+/// A backup ready to be scanned. Archives are extracted to a temporary directory, removed
+/// on drop.
+pub struct OpenedBackup {
+    /// The directory holding `level.dat`, or the backup root if there is none
+    pub world_root: PathBuf,
+    temp_dir: Option<PathBuf>,
+}
+
+impl Drop for OpenedBackup {
+    fn drop(&mut self) {
+        if let Some(temp_dir) = &self.temp_dir {
+            let _ = std::fs::remove_dir_all(temp_dir);
+        }
+    }
+}
+
+/// The shallowest directory under `root` containing a `level.dat`.
+fn find_world_root(root: &Path) -> PathBuf {
+    WalkDir::new(root)
+        .max_depth(3)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == "level.dat")
+        .min_by_key(|entry| entry.depth())
+        .and_then(|entry| entry.path().parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| root.to_path_buf())
+}
+
+impl Backup {
+    pub fn open(&self) -> Result<OpenedBackup, String> {
+        if self.kind == BackupKind::Directory {
+            return Ok(OpenedBackup {
+                world_root: find_world_root(&self.path),
+                temp_dir: None,
+            });
+        }
+
+        let temp_dir = std::env::temp_dir().join(format!(
+            "nbt-sniffer-history-{}-{}",
+            std::process::id(),
+            self.name
+        ));
+        // Removes the directory again if extraction fails
+        let mut opened = OpenedBackup {
+            world_root: temp_dir.clone(),
+            temp_dir: Some(temp_dir.clone()),
+        };
+        let error = |e: &dyn std::fmt::Display| {
+            format!("failed to extract backup '{}': {e}", self.path.display())
+        };
+        std::fs::create_dir_all(&temp_dir).map_err(|e| error(&e))?;
+        let file = BufReader::new(File::open(&self.path).map_err(|e| error(&e))?);
+        match self.kind {
+            BackupKind::Zip => zip::ZipArchive::new(file)
+                .and_then(|mut archive| archive.extract(&temp_dir))
+                .map_err(|e| error(&e))?,
+            BackupKind::Tar => tar::Archive::new(file)
+                .unpack(&temp_dir)
+                .map_err(|e| error(&e))?,
+            BackupKind::TarGz => tar::Archive::new(GzDecoder::new(file))
+                .unpack(&temp_dir)
+                .map_err(|e| error(&e))?,
+            BackupKind::Directory => unreachable!(),
+        }
+        opened.world_root = find_world_root(&temp_dir);
+        Ok(opened)
+    }
+}
+
+/// Bytes of location and timestamp entries at the start of a region file.
+const REGION_HEADER_SIZE: usize = 8_192;
+
+/// The size of a file and, for region files, a hash of the header holding the location
+/// and timestamp of every chunk. Files whose signature differs changed for sure; those
+/// with the same signature still need their content compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSignature {
+    size: u64,
+    header_hash: Option<u64>,
+}
+
+impl FileSignature {
+    pub fn of(path: &Path) -> Option<Self> {
+        let size = path.metadata().ok()?.len();
+        let header_hash = if path.extension().is_some_and(|e| e == "mca") {
+            let mut header = Vec::with_capacity(REGION_HEADER_SIZE);
+            File::open(path)
+                .ok()?
+                .take(REGION_HEADER_SIZE as u64)
+                .read_to_end(&mut header)
+                .ok()?;
+            let mut hasher = DefaultHasher::new();
+            hasher.write(&header);
+            Some(hasher.finish())
+        } else {
+            None
+        };
+        Some(Self { size, header_hash })
+    }
+}
+
+/// Hashes the content of a file, to tell whether it changed between backups.
+pub fn file_hash(path: &Path) -> Option<u64> {
+    let mut file = BufReader::new(File::open(path).ok()?);
+    let mut hasher = DefaultHasher::new();
+    loop {
+        let buffer = file.fill_buf().ok()?;
+        if buffer.is_empty() {
+            return Some(hasher.finish());
+        }
+        hasher.write(buffer);
+        let length = buffer.len();
+        file.consume(length);
+    }
+}
+
+#[derive(Debug)]
+struct CachedFile {
+    signature: FileSignature,
+    /// Hashed only once a later backup holds a file with the same signature
+    content_hash: OnceLock<Option<u64>>,
+    /// Where the file was read from, kept until the next backup has been scanned
+    path: PathBuf,
+    counter: Counter,
+}
+
+/// The item counts of each scanned file of a backup, by path relative to the world root,
+/// so that the next backup can reuse those of files that did not change.
+#[derive(Debug, Default)]
+pub struct FileResultCache {
+    entries: HashMap<PathBuf, CachedFile>,
+}
+
+impl FileResultCache {
+    /// The counts of `relative_path` if the file at `path` has the same signature and, only
+    /// then, the same content as the cached one.
+    pub fn get(
+        &self,
+        relative_path: &Path,
+        path: &Path,
+        signature: FileSignature,
+    ) -> Option<&Counter> {
+        let cached = self
+            .entries
+            .get(relative_path)
+            .filter(|cached| cached.signature == signature)?;
+        let cached_hash = (*cached.content_hash.get_or_init(|| file_hash(&cached.path)))?;
+        (file_hash(path)? == cached_hash).then_some(&cached.counter)
+    }
+
+    pub fn insert(
+        &mut self,
+        relative_path: PathBuf,
+        path: PathBuf,
+        signature: FileSignature,
+        counter: Counter,
+    ) {
+        self.entries.insert(
+            relative_path,
+            CachedFile {
+                signature,
+                content_hash: OnceLock::new(),
+                path,
+                counter,
+            },
+        );
+    }
+}
+
+/// The count of one item in one backup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryRow {
+    pub backup: String,
+    /// UTC time of the backup, in RFC 3339 format
+    pub timestamp: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbt: Option<String>,
+    pub count: u64,
+}
+
+/// Builds one row per backup for every item matched in any backup, with a zero count in the
+/// backups where it is missing. Items are told apart by ID, and by NBT too when `with_nbt`.
+pub fn history_rows(points: &[(Backup, Counter)], with_nbt: bool) -> Vec<HistoryRow> {
+    let totals: Vec<BTreeMap<(&str, Option<&ItemComponents>), u64>> = points
+        .iter()
+        .map(|(_, counter)| {
+            let mut totals = BTreeMap::new();
+            for (key, &count) in counter.detailed_counts() {
+                let nbt = key.components.as_ref().filter(|_| with_nbt);
+                *totals.entry((key.id.as_str(), nbt)).or_default() += count;
+            }
+            totals
+        })
+        .collect();
+    let items: BTreeSet<_> = totals.iter().flat_map(BTreeMap::keys).collect();
+
+    let mut rows = Vec::new();
+    for ((backup, _), totals) in points.iter().zip(&totals) {
+        let timestamp = format_utc_timestamp(backup.timestamp);
+        for &item in &items {
+            rows.push(HistoryRow {
+                backup: backup.name.clone(),
+                timestamp: timestamp.clone(),
+                id: item.0.to_string(),
+                nbt: item.1.map(|components| components.snbt().to_string()),
+                count: totals.get(item).copied().unwrap_or_default(),
+            });
+        }
+    }
+    rows
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_utc_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_date::civil_from_days((secs / 86_400) as i64);
+    let seconds_of_day = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3_600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(format_utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc_timestamp(1_718_454_645), "2024-06-15T12:30:45Z");
+    }
+
+    #[test]
+    fn fills_missing_items_with_zero() {
+        let backup = |name: &str, timestamp| Backup {
+            path: PathBuf::from(name),
+            name: name.to_string(),
+            kind: BackupKind::Directory,
+            timestamp,
+        };
+        let counter = |id: &str, count| {
+            let mut counter = Counter::new();
+            counter.add(id.to_string(), None, count);
+            counter
+        };
+        let points = [
+            (backup("a", 0), counter("minecraft:diamond", 3)),
+            (backup("b", 3_600), counter("minecraft:emerald", 5)),
+        ];
+        let rows: Vec<_> = history_rows(&points, false)
+            .into_iter()
+            .map(|row| (row.backup, row.id, row.count))
+            .collect();
+        let expected = [
+            ("a", "minecraft:diamond", 3),
+            ("a", "minecraft:emerald", 0),
+            ("b", "minecraft:diamond", 0),
+            ("b", "minecraft:emerald", 5),
+        ]
+        .map(|(backup, id, count)| (backup.to_string(), id.to_string(), count));
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn reuses_results_of_unchanged_files() {
+        let dir =
+            std::env::temp_dir().join(format!("nbt-sniffer-history-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, content: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            (path.clone(), FileSignature::of(&path).unwrap())
+        };
+        let (old, old_signature) = file("old.dat", b"abc");
+        let (same, same_signature) = file("same.dat", b"abc");
+        let (edited, edited_signature) = file("edited.dat", b"abd");
+        let (longer, longer_signature) = file("longer.dat", b"abcd");
+
+        let mut cache = FileResultCache::default();
+        let relative = Path::new("playerdata/a.dat");
+        cache.insert(relative.to_path_buf(), old, old_signature, Counter::new());
+        assert!(cache.get(relative, &same, same_signature).is_some());
+        assert!(cache.get(relative, &edited, edited_signature).is_none());
+        assert_ne!(longer_signature, old_signature);
+        assert!(cache.get(relative, &longer, longer_signature).is_none());
+        assert!(
+            cache
+                .get(Path::new("playerdata/b.dat"), &same, same_signature)
+                .is_none()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn region_signatures_include_the_header() {
+        let dir =
+            std::env::temp_dir().join(format!("nbt-sniffer-signature-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let signature = |header_byte: u8| {
+            let path = dir.join("r.0.0.mca");
+            let mut data = vec![0u8; REGION_HEADER_SIZE * 2];
+            data[4_100] = header_byte;
+            std::fs::write(&path, data).unwrap();
+            FileSignature::of(&path).unwrap()
+        };
+        assert_eq!(signature(1), signature(1));
+        assert_ne!(signature(1), signature(2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::LazyLock;
+
+use simdnbt::borrow::NbtCompound;
+
+use crate::nbt_path::NbtPath;
+
+/// An NBT path holding items, relative to the holder (block entity, entity or player).
+pub struct ItemField {
+    /// Holder IDs the field applies to; empty for every holder of this kind
+    pub holders: &'static [&'static str],
+    pub path: &'static str,
+}
+
+const fn any(path: &'static str) -> ItemField {
+    ItemField { holders: &[], path }
+}
+
+const fn only(holders: &'static [&'static str], path: &'static str) -> ItemField {
+    ItemField { holders, path }
+}
+
+const SPAWNERS: &[&str] = &["minecraft:mob_spawner"];
+const TRADERS: &[&str] = &["minecraft:villager", "minecraft:wandering_trader"];
+
+/// Item-bearing fields of block entities, across versions.
+pub const BLOCK_ENTITY_ITEM_FIELDS: &[ItemField] = &[
+    // Chests, barrels, hoppers, furnaces, shulker boxes, campfires, crafters, chiseled bookshelves...
+    any("Items[]"),
+    // Brushable blocks (suspicious sand and gravel) and decorated pots
+    any("item"),
+    only(&["minecraft:jukebox"], "RecordItem"),
+    only(&["minecraft:lectern"], "Book"),
+    only(SPAWNERS, "SpawnData.entity.equipment.*"),
+    only(SPAWNERS, "SpawnData.entity.HandItems[]"),
+    only(SPAWNERS, "SpawnData.entity.ArmorItems[]"),
+    only(SPAWNERS, "SpawnPotentials[].data.entity.equipment.*"),
+    only(SPAWNERS, "SpawnPotentials[].data.entity.HandItems[]"),
+    only(SPAWNERS, "SpawnPotentials[].data.entity.ArmorItems[]"),
+    only(
+        &["minecraft:trial_spawner"],
+        "spawn_data.entity.equipment.*",
+    ),
+    only(&["minecraft:vault"], "shared_data.display_item"),
+];
+
+/// Item-bearing fields of entities, across versions.
+pub const ENTITY_ITEM_FIELDS: &[ItemField] = &[
+    // Container minecarts and boats, donkeys and llamas with chests
+    any("Items[]"),
+    // Villagers, piglins and allays
+    any("Inventory[]"),
+    // Dropped items, item frames, thrown items and fireworks
+    any("Item"),
+    // Mob and armor stand equipment since 1.21.5
+    any("equipment.*"),
+    // Equipment before 1.21.5, with `{}` for empty slots
+    any("HandItems[]"),
+    any("ArmorItems[]"),
+    any("body_armor_item"),
+    any("SaddleItem"),
+    any("ArmorItem"),
+    any("DecorItem"),
+    only(TRADERS, "Offers.Recipes[].buy"),
+    only(TRADERS, "Offers.Recipes[].buyB"),
+    only(TRADERS, "Offers.Recipes[].sell"),
+];
+
+/// Item-bearing fields of player data.
+pub const PLAYER_ITEM_FIELDS: &[ItemField] =
+    &[any("Inventory[]"), any("EnderItems[]"), any("equipment.*")];
+
+/// An item field table with its paths parsed once.
+pub struct HolderItemFields {
+    fields: Vec<(&'static [&'static str], NbtPath)>,
+}
+
+impl HolderItemFields {
+    pub fn new(table: &[ItemField]) -> Self {
+        Self {
+            fields: table
+                .iter()
+                .map(|field| {
+                    let path = NbtPath::parse(field.path)
+                        .unwrap_or_else(|e| panic!("invalid item field '{}': {e}", field.path));
+                    (field.holders, path)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns every item compound stored directly in `holder`, whose ID is `holder_id`.
+    pub fn items<'a: 'tape, 'tape>(
+        &self,
+        holder_id: &str,
+        holder: NbtCompound<'a, 'tape>,
+    ) -> Vec<NbtCompound<'a, 'tape>> {
+        self.fields
+            .iter()
+            .filter(|(holders, _)| holders.is_empty() || holders.contains(&holder_id))
+            .flat_map(|(_, path)| path.resolve_compounds(holder))
+            .collect()
+    }
+}
+
+pub static BLOCK_ENTITY_FIELDS: LazyLock<HolderItemFields> =
+    LazyLock::new(|| HolderItemFields::new(BLOCK_ENTITY_ITEM_FIELDS));
+pub static ENTITY_FIELDS: LazyLock<HolderItemFields> =
+    LazyLock::new(|| HolderItemFields::new(ENTITY_ITEM_FIELDS));
+pub static PLAYER_FIELDS: LazyLock<HolderItemFields> =
+    LazyLock::new(|| HolderItemFields::new(PLAYER_ITEM_FIELDS));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_utils::snbt_to_binary;
+    use std::io::Cursor;
+
+    fn item_ids(fields: &HolderItemFields, holder_id: &str, snbt: &str) -> Vec<String> {
+        let bytes = snbt_to_binary(snbt);
+        let nbt = simdnbt::borrow::read(&mut Cursor::new(&bytes[..]))
+            .unwrap()
+            .unwrap();
+        fields
+            .items(holder_id, nbt.as_compound())
+            .iter()
+            .filter_map(|item| item.string("id").map(|id| id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn all_tables_parse() {
+        for table in [
+            BLOCK_ENTITY_ITEM_FIELDS,
+            ENTITY_ITEM_FIELDS,
+            PLAYER_ITEM_FIELDS,
+        ] {
+            HolderItemFields::new(table);
+        }
+    }
+
+    #[test]
+    fn legacy_equipment_and_trades() {
+        assert_eq!(
+            item_ids(
+                &ENTITY_FIELDS,
+                "minecraft:skeleton",
+                r#"{HandItems:[{id:"minecraft:bow",Count:1b},{}],ArmorItems:[{},{},{},{id:"minecraft:leather_helmet",Count:1b}]}"#,
+            ),
+            ["minecraft:bow", "minecraft:leather_helmet"]
+        );
+        assert_eq!(
+            item_ids(
+                &ENTITY_FIELDS,
+                "minecraft:villager",
+                r#"{Offers:{Recipes:[{buy:{id:"minecraft:emerald",count:20},buyB:{id:"minecraft:book",count:1},sell:{id:"minecraft:enchanted_book",count:1}}]}}"#,
+            ),
+            [
+                "minecraft:emerald",
+                "minecraft:book",
+                "minecraft:enchanted_book"
+            ]
+        );
+    }
+
+    #[test]
+    fn fields_are_limited_to_their_holders() {
+        let spawner = r#"{SpawnData:{entity:{id:"minecraft:zombie",equipment:{mainhand:{id:"minecraft:iron_sword",count:1}}}}}"#;
+        assert_eq!(
+            item_ids(&BLOCK_ENTITY_FIELDS, "minecraft:mob_spawner", spawner),
+            ["minecraft:iron_sword"]
+        );
+        assert!(item_ids(&BLOCK_ENTITY_FIELDS, "minecraft:chest", spawner).is_empty());
+    }
+}
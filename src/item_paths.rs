@@ -0,0 +1,202 @@
+use simdnbt::borrow::NbtCompound;
+
+use crate::nbt_path::{NbtPath, PathSegment};
+use crate::nbt_utils;
+
+/// The key holding the slot of a wrapper entry, e.g. in `minecraft:container`, whose item
+/// sits under a separate key instead of carrying its own `Slot`.
+const NBT_KEY_WRAPPER_SLOT: &str = "slot";
+
+/// Paths, relative to an item compound, under which vanilla stores other items.
+pub const VANILLA_NESTED_ITEM_PATHS: &[&str] = &[
+    // Shulker boxes and other containers picked up with their contents
+    r#"components."minecraft:container"[].item"#,
+    r#"components."minecraft:bundle_contents"[]"#,
+    // Arrows and fireworks loaded into a crossbow
+    r#"components."minecraft:charged_projectiles"[]"#,
+    r#"components."minecraft:use_remainder""#,
+    // Block entity data kept on the item, e.g. a chest picked up in creative
+    r#"components."minecraft:block_entity_data".Items[]"#,
+    // Entity data on spawn eggs, item frames and armor stands
+    r#"components."minecraft:entity_data".Item"#,
+    r#"components."minecraft:entity_data".Items[]"#,
+    r#"components."minecraft:entity_data".equipment.*"#,
+    r#"components."minecraft:entity_data".HandItems[]"#,
+    r#"components."minecraft:entity_data".ArmorItems[]"#,
+    // Item stacks saved before 1.20.5
+    "tag.BlockEntityTag.Items[]",
+    "tag.Items[]",
+    "tag.ChargedProjectiles[]",
+    "tag.EntityTag.Item",
+];
+
+/// The set of paths searched for items nested inside another item.
+#[derive(Debug, Clone)]
+pub struct NestedItemPaths {
+    paths: Vec<NbtPath>,
+}
+
+impl NestedItemPaths {
+    pub fn new() -> Self {
+        Self { paths: Vec::new() }
+    }
+
+    /// The vanilla paths from `VANILLA_NESTED_ITEM_PATHS`.
+    pub fn vanilla() -> Self {
+        let mut registry = Self::new();
+        for path in VANILLA_NESTED_ITEM_PATHS {
+            registry
+                .add(path)
+                .expect("vanilla nested item paths should parse");
+        }
+        registry
+    }
+
+    /// Adds a path in `NbtPath` syntax. It must lead to item compounds, using `[]` for lists
+    /// of items and `*` for compounds keyed by slot.
+    pub fn add(&mut self, path: &str) -> Result<(), String> {
+        let path = NbtPath::parse(path)?;
+        if !self.paths.contains(&path) {
+            self.paths.push(path);
+        }
+        Ok(())
+    }
+
+    pub fn paths(&self) -> &[NbtPath] {
+        &self.paths
+    }
+
+    /// Returns every item compound nested directly inside `item`, with its slot.
+    pub fn nested_items<'a: 'tape, 'tape>(
+        &self,
+        item: NbtCompound<'a, 'tape>,
+    ) -> Vec<NestedItem<'a, 'tape>> {
+        self.paths
+            .iter()
+            .flat_map(|path| resolve_items(path, item))
+            .collect()
+    }
+}
+
+/// An item found under one of the `NestedItemPaths`.
+pub struct NestedItem<'a, 'tape> {
+    pub item: NbtCompound<'a, 'tape>,
+    /// The `Slot` of the item, or else the `slot` of the entry wrapping it
+    pub slot: Option<i8>,
+}
+
+/// Resolves `path` in `root`. When the path ends in a key, the compounds holding that key
+/// are resolved first, so their `slot` can be used for items without a `Slot` of their own.
+fn resolve_items<'a: 'tape, 'tape>(
+    path: &NbtPath,
+    root: NbtCompound<'a, 'tape>,
+) -> Vec<NestedItem<'a, 'tape>> {
+    let own_slot = |item: &NbtCompound| item.byte(nbt_utils::NBT_KEY_SLOT);
+    let Some((PathSegment::Key(key), parent_segments)) = path.segments.split_last() else {
+        return path
+            .resolve_compounds(root)
+            .into_iter()
+            .map(|item| NestedItem {
+                slot: own_slot(&item),
+                item,
+            })
+            .collect();
+    };
+
+    let parent = NbtPath {
+        segments: parent_segments.to_vec(),
+    };
+    parent
+        .resolve_compounds(root)
+        .into_iter()
+        .filter_map(|wrapper| {
+            let item = wrapper.compound(key)?;
+            let slot = own_slot(&item).or_else(|| {
+                wrapper
+                    .int(NBT_KEY_WRAPPER_SLOT)
+                    .and_then(|slot| i8::try_from(slot).ok())
+            });
+            Some(NestedItem { item, slot })
+        })
+        .collect()
+}
+
+impl Default for NestedItemPaths {
+    fn default() -> Self {
+        Self::vanilla()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_utils::snbt_to_binary;
+    use std::io::Cursor;
+
+    #[test]
+    fn vanilla_paths_parse() {
+        assert_eq!(
+            NestedItemPaths::vanilla().paths().len(),
+            VANILLA_NESTED_ITEM_PATHS.len()
+        );
+    }
+
+    #[test]
+    fn finds_items_under_every_vanilla_path() {
+        let bytes = snbt_to_binary(
+            r#"{id:"minecraft:crossbow",components:{"minecraft:charged_projectiles":[{id:"minecraft:arrow",count:1}],"minecraft:use_remainder":{id:"minecraft:bowl",count:1},"minecraft:block_entity_data":{id:"minecraft:chest",Items:[{Slot:0b,id:"minecraft:dirt",count:3}]},"minecraft:entity_data":{id:"minecraft:armor_stand",equipment:{head:{id:"minecraft:carved_pumpkin",count:1}}}}}"#,
+        );
+        let nbt = simdnbt::borrow::read(&mut Cursor::new(&bytes[..]))
+            .unwrap()
+            .unwrap();
+
+        let mut ids: Vec<_> = NestedItemPaths::vanilla()
+            .nested_items(nbt.as_compound())
+            .iter()
+            .filter_map(|nested| nested.item.string("id").map(|id| id.to_string()))
+            .collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                "minecraft:arrow",
+                "minecraft:bowl",
+                "minecraft:carved_pumpkin",
+                "minecraft:dirt"
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_the_slot_from_the_container_entry() {
+        let bytes = snbt_to_binary(
+            r#"{id:"minecraft:shulker_box",components:{"minecraft:container":[{slot:5,item:{id:"minecraft:dirt",count:1}}],"minecraft:block_entity_data":{id:"minecraft:chest",Items:[{Slot:2b,id:"minecraft:stone",count:1}]}}}"#,
+        );
+        let nbt = simdnbt::borrow::read(&mut Cursor::new(&bytes[..]))
+            .unwrap()
+            .unwrap();
+
+        let mut slots: Vec<_> = NestedItemPaths::vanilla()
+            .nested_items(nbt.as_compound())
+            .iter()
+            .map(|nested| (nested.item.string("id").unwrap().to_string(), nested.slot))
+            .collect();
+        slots.sort();
+        assert_eq!(
+            slots,
+            [
+                ("minecraft:dirt".to_string(), Some(5)),
+                ("minecraft:stone".to_string(), Some(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn add_rejects_invalid_and_duplicate_paths() {
+        let mut registry = NestedItemPaths::new();
+        registry.add("tag.Items[]").unwrap();
+        registry.add("tag.Items[]").unwrap();
+        assert_eq!(registry.paths().len(), 1);
+        assert!(registry.add("tag.").is_err());
+    }
+}
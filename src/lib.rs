@@ -1,6 +1,23 @@
+pub mod chunk_filter;
+pub mod civil_date;
 pub mod cli;
 pub mod counter;
+pub mod diff;
+pub mod enchantments;
+pub mod filter;
+pub mod heatmap;
+pub mod history;
+pub mod holder_fields;
+pub mod item_paths;
+pub mod nbt_path;
 pub mod nbt_utils;
+pub mod occurrences;
+pub mod region_cache;
+pub mod snapshot;
+pub mod sqlite_export;
+pub mod tags;
+pub mod text_component;
+pub mod trades;
 pub mod tree;
 pub mod view;
 
@@ -8,21 +25,31 @@ use std::{
     collections::HashMap,
     io::{Cursor, Read},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
-use cli::{CliArgs, ItemFilter};
-use counter::{Counter, CounterMap};
+use cli::CliArgs;
+use counter::{ComponentsInterner, Counter, CounterMap, ItemKey};
+use filter::{ItemContext, ItemQuery};
 use flate2::read::GzDecoder;
+use heatmap::ChunkCounts;
+use holder_fields::{BLOCK_ENTITY_FIELDS, ENTITY_FIELDS, PLAYER_FIELDS};
 use mca::RegionReader;
 use nbt_utils::{convert_simdnbt_to_valence_nbt, get_entity_pos_string};
+use occurrences::{Holder, Occurrence};
 use ptree::print_tree;
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use region_cache::{CachedChunk, ChunkStamp, RegionCache};
 use serde::{Deserialize, Serialize};
+use trades::{TradeOffer, extract_trade_offers};
 use tree::ItemSummaryNode;
 use valence_nbt::Value;
 
 const CHUNK_PER_REGION_SIDE: usize = 32;
+/// Smallest run of consecutive chunks of a region file scanned by one thread.
+const CHUNKS_PER_WORK_UNIT: usize = 16;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Scope {
     pub dimension: String,
     pub data_type: DataType,
@@ -82,35 +109,185 @@ pub fn list_mca_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
     Ok(mca_files)
 }
 
+/// Everything collected while scanning a single task.
+#[derive(Debug, Default)]
+pub struct TaskOutput {
+    pub counter: Counter,
+    /// Villager and wandering trader offers, only collected with `--trades`
+    pub trades: Vec<TradeOffer>,
+    /// Matched items, collected for the `ndjson` format, `--export-sqlite` and
+    /// `--snapshot-locations`
+    pub occurrences: Vec<Occurrence>,
+    /// Per-source summary trees, collected instead of printed with the HTML and Markdown formats
+    pub source_trees: Vec<ItemSummaryNode>,
+    /// Matched item counts per chunk, only collected with `--heatmap` and `--cache-dir`
+    pub chunk_counts: ChunkCounts,
+    /// Global coordinates of the chunk being scanned, for region files
+    pub current_chunk: Option<(i32, i32)>,
+    /// Components of the counted items, converted once per distinct components
+    pub components: ComponentsInterner,
+}
+
+impl TaskOutput {
+    /// Appends the output of a part of the same task, such as one chunk of a region file.
+    pub fn append(&mut self, other: TaskOutput) {
+        self.counter.append(other.counter);
+        self.trades.extend(other.trades);
+        let offset = self.occurrences.len();
+        self.occurrences
+            .extend(other.occurrences.into_iter().map(|occurrence| Occurrence {
+                parent: occurrence.parent.map(|parent| parent + offset),
+                ..occurrence
+            }));
+        self.source_trees.extend(other.source_trees);
+        for (chunk, count) in other.chunk_counts {
+            *self.chunk_counts.entry(chunk).or_default() += count;
+        }
+    }
+}
+
+/// What became of one chunk of a region file.
+enum ChunkScan {
+    /// Left out by the chunk filters
+    Skipped,
+    /// Unchanged since its results were cached
+    Cached,
+    Scanned(Box<TaskOutput>),
+}
+
+/// The merged output of all scan tasks.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub counter_map: CounterMap,
+    pub trades: Vec<TradeOffer>,
+    pub source_trees: Vec<ItemSummaryNode>,
+    /// Matched items left by the tasks, in task order
+    pub occurrences: Vec<Occurrence>,
+    /// Matched item counts per chunk, by dimension
+    pub chunk_counts: HashMap<String, ChunkCounts>,
+}
+
+impl ScanResult {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wraps the output of a task.
+    pub fn from_task_output(scope: Scope, output: TaskOutput) -> Self {
+        let mut chunk_counts = HashMap::new();
+        if !output.chunk_counts.is_empty() {
+            chunk_counts.insert(scope.dimension.clone(), output.chunk_counts);
+        }
+        let mut counter_map = CounterMap::new();
+        counter_map.entry_counter(scope).append(output.counter);
+        Self {
+            counter_map,
+            trades: output.trades,
+            source_trees: output.source_trees,
+            occurrences: output.occurrences,
+            chunk_counts,
+        }
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        self.counter_map.append(other.counter_map);
+        self.trades.extend(other.trades);
+        self.source_trees.extend(other.source_trees);
+        self.occurrences.extend(other.occurrences);
+        for (dimension, chunks) in other.chunk_counts {
+            let counts = self.chunk_counts.entry(dimension).or_default();
+            for (chunk, count) in chunks {
+                *counts.entry(chunk).or_default() += count;
+            }
+        }
+        self
+    }
+}
+
 pub fn process_task(
     task: ScanTask,
-    queries: &[ItemFilter],
+    queries: &ItemQuery,
+    args: &CliArgs,
+    user_cache: &HashMap<String, String>,
+) -> ScanResult {
+    let output = scan_task(&task, queries, args, user_cache);
+    ScanResult::from_task_output(task.scope, output)
+}
+
+/// Scans the file of a single task.
+pub fn scan_task(
+    task: &ScanTask,
+    queries: &ItemQuery,
     args: &CliArgs,
     user_cache: &HashMap<String, String>,
-) -> CounterMap {
-    let mut counter = Counter::new();
+) -> TaskOutput {
+    let mut output = TaskOutput::default();
     match task.scope.data_type {
-        DataType::BlockEntity => process_region_file(&task, queries, args, &mut counter),
-        DataType::Entity => process_entities_file(&task, queries, args, &mut counter),
-        DataType::Player => process_player_file(&task, queries, args, &mut counter, user_cache),
+        DataType::BlockEntity => process_region_file(task, queries, args, &mut output),
+        DataType::Entity => process_entities_file(task, queries, args, &mut output),
+        DataType::Player => process_player_file(task, queries, args, &mut output, user_cache),
     }
-    let mut map = CounterMap::new();
-    map.merge_scope(task.scope, &counter);
-    map
+    output
 }
 
 /// Generic function to process a region file, iterating through its chunks
 /// and applying a given chunk processing function.
 fn process_any_region_file<F>(
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
     process_chunk_fn: F,
 ) where
-    F: Fn(&mca::RawChunk, usize, usize, &ScanTask, &[ItemFilter], &CliArgs, &mut Counter),
+    F: Fn(&mca::RawChunk, usize, usize, &ScanTask, &ItemQuery, &CliArgs, &mut TaskOutput) + Sync,
 {
     let region_file_path = &task.path;
+    let region = heatmap::region_coordinates(region_file_path);
+    let chunk_coordinates = |cx: usize, cy: usize| {
+        region.map(|(region_x, region_z)| {
+            let side = CHUNK_PER_REGION_SIDE as i32;
+            (region_x * side + cx as i32, region_z * side + cy as i32)
+        })
+    };
+    // Entities region files store no InhabitedTime, it is read from the block region file
+    // the first time a chunk passes the other filters
+    let inhabited_path = cli_args
+        .min_inhabited_ticks
+        .filter(|_| task.scope.data_type == DataType::Entity)
+        .and_then(|_| chunk_filter::block_region_path(region_file_path));
+    let inhabited_times = OnceLock::new();
+    let accepts_chunk = |index: usize, stamp: ChunkStamp| {
+        cli_args.accepts_chunk_timestamp(stamp.timestamp)
+            && inhabited_path.as_ref().is_none_or(|path| {
+                let times = inhabited_times
+                    .get_or_init(|| chunk_filter::load_inhabited_times(path, cli_args.verbose));
+                times.get(&index).copied().unwrap_or_default()
+                    >= cli_args.min_inhabited_ticks.unwrap_or_default()
+            })
+    };
+    let mut cache = cli_args.cache_dir.as_deref().map(|cache_dir| {
+        let settings = region_cache::settings_fingerprint(item_queries, cli_args);
+        RegionCache::open(cache_dir, region_file_path, settings)
+    });
+
+    if let Some(cache) = &cache
+        && cache.file_unchanged()
+    {
+        if cli_args.verbose {
+            eprintln!("Using cached results for {}", region_file_path.display());
+        }
+        for (index, chunk) in cache.previous_chunks() {
+            if !accepts_chunk(index, chunk.stamp()) {
+                continue;
+            }
+            output.current_chunk =
+                chunk_coordinates(index % CHUNK_PER_REGION_SIDE, index / CHUNK_PER_REGION_SIDE);
+            chunk.add_to(output);
+        }
+        output.current_chunk = None;
+        return;
+    }
+
     let data = match std::fs::read(region_file_path) {
         Ok(d) => d,
         Err(e) => {
@@ -134,11 +311,28 @@ fn process_any_region_file<F>(
         }
     };
 
-    for cy in 0..CHUNK_PER_REGION_SIDE {
-        for cx in 0..CHUNK_PER_REGION_SIDE {
+    // Chunks are scanned in parallel, each into its own output, so that idle threads can take
+    // over part of a large region file; outputs are merged in chunk order.
+    let scanned_chunks: Vec<_> = (0..CHUNK_PER_REGION_SIDE * CHUNK_PER_REGION_SIDE)
+        .into_par_iter()
+        .with_min_len(CHUNKS_PER_WORK_UNIT)
+        .map(|index| {
+            // The reader checked that the header is complete
+            let stamp = ChunkStamp::from_header(&data, index)?;
+            if stamp.location == 0 {
+                return None; // No chunk data
+            }
+            if !accepts_chunk(index, stamp) {
+                return Some((index, stamp, ChunkScan::Skipped));
+            }
+            if cache.as_ref().is_some_and(|cache| cache.has(index, stamp)) {
+                return Some((index, stamp, ChunkScan::Cached));
+            }
+
+            let (cx, cy) = (index % CHUNK_PER_REGION_SIDE, index / CHUNK_PER_REGION_SIDE);
             let chunk_data = match region_reader.get_chunk(cx, cy) {
                 Ok(Some(c)) => c,
-                Ok(None) => continue, // No chunk data
+                Ok(None) => return None, // No chunk data
                 Err(e) => {
                     if cli_args.verbose {
                         eprintln!(
@@ -146,10 +340,71 @@ fn process_any_region_file<F>(
                             region_file_path.display()
                         );
                     }
-                    continue;
+                    return None;
                 }
             };
-            process_chunk_fn(&chunk_data, cx, cy, task, item_queries, cli_args, counter);
+            let mut chunk_output = TaskOutput {
+                current_chunk: chunk_coordinates(cx, cy),
+                ..TaskOutput::default()
+            };
+            process_chunk_fn(
+                &chunk_data,
+                cx,
+                cy,
+                task,
+                item_queries,
+                cli_args,
+                &mut chunk_output,
+            );
+            Some((index, stamp, ChunkScan::Scanned(Box::new(chunk_output))))
+        })
+        .collect();
+
+    let mut reused_chunks = 0;
+    let mut skipped_chunks = 0;
+    for (index, stamp, scan) in scanned_chunks.into_iter().flatten() {
+        output.current_chunk =
+            chunk_coordinates(index % CHUNK_PER_REGION_SIDE, index / CHUNK_PER_REGION_SIDE);
+        match (scan, &mut cache) {
+            (ChunkScan::Skipped, cache) => {
+                // Kept in the cache for runs with other chunk filters
+                if let Some(cache) = cache
+                    && cache.reuse(index, stamp).is_none()
+                {
+                    cache.mark_incomplete();
+                }
+                skipped_chunks += 1;
+            }
+            (ChunkScan::Cached, Some(cache)) => {
+                if let Some(chunk) = cache.reuse(index, stamp) {
+                    chunk.add_to(output);
+                }
+                reused_chunks += 1;
+            }
+            (ChunkScan::Scanned(chunk_output), Some(cache)) => cache
+                .insert(index, CachedChunk::from_output(stamp, *chunk_output))
+                .add_to(output),
+            (ChunkScan::Scanned(chunk_output), None) => output.append(*chunk_output),
+            (ChunkScan::Cached, None) => {}
+        }
+    }
+    output.current_chunk = None;
+
+    if cli_args.verbose && skipped_chunks > 0 {
+        eprintln!(
+            "Skipped {skipped_chunks} chunks of {} by modification or inhabited time",
+            region_file_path.display()
+        );
+    }
+    if let Some(cache) = cache {
+        if cli_args.verbose {
+            eprintln!(
+                "Reused {reused_chunks} cached chunks of {}",
+                region_file_path.display()
+            );
+        }
+        if let Err(e) = cache.save() {
+            eprintln!("Warning: {e}");
         }
     }
 }
@@ -157,32 +412,32 @@ fn process_any_region_file<F>(
 /// Scans one region file for block entities.
 pub fn process_region_file(
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
     process_any_region_file(
         task,
         item_queries,
         cli_args,
-        counter,
+        output,
         process_chunk_for_block_entities,
     );
 }
 
 /// Scans one region file for regular entities.
-/// Also merges all found items into the global `counter`.
+/// Also merges all found items into the task `output`.
 pub fn process_entities_file(
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
     process_any_region_file(
         task,
         item_queries,
         cli_args,
-        counter,
+        output,
         process_chunk_for_entities,
     );
 }
@@ -194,13 +449,13 @@ fn process_chunk_nbt_list<F>(
     cy: usize,
     cx: usize,
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
     nbt_list_name: &str,
     process_nbt_compound_fn: F,
 ) where
-    F: Fn(simdnbt::borrow::NbtCompound, &ScanTask, &[ItemFilter], &CliArgs, &mut Counter),
+    F: Fn(simdnbt::borrow::NbtCompound, &ScanTask, &ItemQuery, &CliArgs, &mut TaskOutput),
 {
     let region_file_path = &task.path;
     let decompressed_data = match chunk_data.decompress() {
@@ -238,13 +493,20 @@ fn process_chunk_nbt_list<F>(
         }
     };
 
+    if task.scope.data_type == DataType::BlockEntity
+        && let Some(min_ticks) = cli_args.min_inhabited_ticks
+        && chunk_filter::inhabited_time(&nbt_root.as_compound()).unwrap_or_default() < min_ticks
+    {
+        return;
+    }
+
     let Some(compounds_list) = nbt_root.list(nbt_list_name).and_then(|l| l.compounds()) else {
         // If the list is not found or is not a list of compounds, this is normal (e.g., chunk with no relevant entities).
         return;
     };
 
     for nbt_compound in compounds_list {
-        process_nbt_compound_fn(nbt_compound, task, item_queries, cli_args, counter);
+        process_nbt_compound_fn(nbt_compound, task, item_queries, cli_args, output);
     }
 }
 
@@ -254,9 +516,9 @@ fn process_chunk_for_block_entities(
     cx: usize,
     cy: usize,
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
     process_chunk_nbt_list(
         chunk_data,
@@ -265,7 +527,7 @@ fn process_chunk_for_block_entities(
         task,
         item_queries,
         cli_args,
-        counter,
+        output,
         "block_entities", // NBT key for block entities in a chunk
         process_block_entity,
     );
@@ -277,9 +539,9 @@ fn process_chunk_for_entities(
     cx: usize,
     cy: usize,
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
     process_chunk_nbt_list(
         chunk_data,
@@ -288,7 +550,7 @@ fn process_chunk_for_entities(
         task,
         item_queries,
         cli_args,
-        counter,
+        output,
         "Entities", // NBT key for entities in a chunk
         process_single_entity,
     );
@@ -297,9 +559,9 @@ fn process_chunk_for_entities(
 /// Processes a player data file (.dat or level.dat for the player section).
 fn process_player_file(
     task: &ScanTask,
-    queries: &[ItemFilter],
+    queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
     user_cache: &HashMap<String, String>,
 ) {
     let file_path = &task.path;
@@ -417,7 +679,7 @@ fn process_player_file(
             task,
             queries,
             cli_args,
-            counter,
+            output,
             &source_id,
             &location_str,
         );
@@ -458,60 +720,38 @@ pub fn extract_single_player_uuid_from_level_dat(
 fn process_player_nbt_compound(
     player_nbt: simdnbt::borrow::NbtCompound,
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
     source_id: &str,
     location_str: &str,
 ) {
-    let mut summary_nodes = Vec::new();
-
-    if let Some(item_list) = player_nbt
-        .list(nbt_utils::NBT_KEY_INVENTORY)
-        .and_then(|l| l.compounds())
-    {
-        for item_compound in item_list {
-            collect_summary_node(
-                &item_compound,
-                cli_args,
-                item_queries,
-                &mut summary_nodes,
-                counter,
-            );
-        }
-    }
-
-    if let Some(item_list) = player_nbt
-        .list(nbt_utils::NBT_KEY_ENDER_ITEMS)
-        .and_then(|l| l.compounds())
-    {
-        for item_compound in item_list {
-            collect_summary_node(
-                &item_compound,
-                cli_args,
-                item_queries,
-                &mut summary_nodes,
-                counter,
-            );
-        }
+    if !item_queries.matches_holder("minecraft:player", player_nbt) {
+        return;
     }
 
-    if let Some(holder_compound) = player_nbt.compound(nbt_utils::NBT_KEY_EQUIPMENT) {
-        for (_key_in_holder, value_nbt) in holder_compound.iter() {
-            if let Some(actual_item_compound) = value_nbt.compound() {
-                collect_summary_node(
-                    &actual_item_compound,
-                    cli_args,
-                    item_queries,
-                    &mut summary_nodes,
-                    counter,
-                );
-            }
-        }
+    let holder = Holder {
+        scope: &task.scope,
+        id: "minecraft:player",
+        position: location_str,
+        coordinates: nbt_utils::get_entity_pos(&player_nbt),
+        player: Some(source_id),
+    };
+    let mut summary_nodes = Vec::new();
+    for item_compound in PLAYER_FIELDS.items("minecraft:player", player_nbt) {
+        collect_summary_node(
+            &item_compound,
+            &holder,
+            cli_args,
+            item_queries,
+            &mut summary_nodes,
+            output,
+        );
     }
 
     print_per_source_summary_if_enabled(
         cli_args,
+        output,
         &task.scope.dimension,
         source_id,
         location_str,
@@ -519,9 +759,11 @@ fn process_player_nbt_compound(
     );
 }
 
-/// Prints a per-source summary tree if the corresponding CLI flag is enabled.
+/// Prints a per-source summary tree if the corresponding CLI flag is enabled. With the
+/// HTML and Markdown formats, the tree is kept in `output` for the report instead.
 fn print_per_source_summary_if_enabled(
     cli_args: &CliArgs,
+    output: &mut TaskOutput,
     dimension: &str,
     source_id: &str,
     source_location: &str,
@@ -531,7 +773,9 @@ fn print_per_source_summary_if_enabled(
         let root_label = format!("[{dimension}] {source_id} @ {source_location}");
         let mut root = ItemSummaryNode::new_root(root_label, summary_nodes);
         root.collapse_leaves_recursive();
-        if let Err(e) = print_tree(&root) {
+        if cli_args.format.embeds_source_trees() {
+            output.source_trees.push(root);
+        } else if let Err(e) = print_tree(&root) {
             // Handle error from print_tree, e.g., by logging to stderr
             eprintln!("Error printing tree summary for {source_id}: {e}");
         }
@@ -542,9 +786,9 @@ fn print_per_source_summary_if_enabled(
 fn process_single_entity(
     entity_nbt: simdnbt::borrow::NbtCompound,
     task: &ScanTask,
-    queries: &[ItemFilter],
+    queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
     let Some(id_str) = entity_nbt.string(nbt_utils::NBT_KEY_ID) else {
         return;
@@ -554,42 +798,34 @@ fn process_single_entity(
         get_entity_pos_string(&entity_nbt).unwrap_or_else(|| "Unknown Position".to_string());
 
     let mut summary_nodes = Vec::new();
-    for list_field_name in &[nbt_utils::NBT_KEY_ITEMS, nbt_utils::NBT_KEY_INVENTORY] {
-        if let Some(item_list) = entity_nbt.list(list_field_name).and_then(|l| l.compounds()) {
-            for item_compound in item_list {
-                collect_summary_node(
-                    &item_compound,
-                    cli_args,
-                    queries,
-                    &mut summary_nodes,
-                    counter,
-                );
-            }
+    if queries.matches_holder(&id, entity_nbt) {
+        let holder = Holder {
+            scope: &task.scope,
+            id: &id,
+            position: &pos_str,
+            coordinates: nbt_utils::get_entity_pos(&entity_nbt),
+            player: None,
+        };
+        for item_compound in ENTITY_FIELDS.items(&id, entity_nbt) {
+            collect_summary_node(
+                &item_compound,
+                &holder,
+                cli_args,
+                queries,
+                &mut summary_nodes,
+                output,
+            );
         }
     }
 
-    if let Some(item_compound) = entity_nbt.compound(nbt_utils::NBT_KEY_ITEM) {
-        collect_summary_node(
-            &item_compound,
-            cli_args,
+    if cli_args.trades {
+        output.trades.extend(extract_trade_offers(
+            entity_nbt,
+            &id,
+            &task.scope.dimension,
+            &pos_str,
             queries,
-            &mut summary_nodes,
-            counter,
-        );
-    }
-
-    if let Some(holder_compound) = entity_nbt.compound(nbt_utils::NBT_KEY_EQUIPMENT) {
-        for (_key_in_holder, value_nbt) in holder_compound.iter() {
-            if let Some(actual_item_compound) = value_nbt.compound() {
-                collect_summary_node(
-                    &actual_item_compound,
-                    cli_args,
-                    queries,
-                    &mut summary_nodes,
-                    counter,
-                );
-            }
-        }
+        ));
     }
 
     if let Some(passengers_list) = entity_nbt
@@ -601,12 +837,13 @@ fn process_single_entity(
             // The passenger's items will be added to the current entity's summary_nodes
             // and the global_counter. This is generally fine as the per-source summary
             // is for the top-level entity being processed from the chunk.
-            process_single_entity(passenger_nbt, task, queries, cli_args, counter);
+            process_single_entity(passenger_nbt, task, queries, cli_args, output);
         }
     }
 
     print_per_source_summary_if_enabled(
         cli_args,
+        output,
         &task.scope.dimension,
         &id,
         &pos_str,
@@ -617,9 +854,9 @@ fn process_single_entity(
 fn process_block_entity(
     block_entity: simdnbt::borrow::NbtCompound,
     task: &ScanTask,
-    item_queries: &[ItemFilter],
+    item_queries: &ItemQuery,
     cli_args: &CliArgs,
-    counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
     let id = block_entity
         .string(nbt_utils::NBT_KEY_ID)
@@ -629,25 +866,33 @@ fn process_block_entity(
     let y = block_entity.int("y").unwrap();
     let z = block_entity.int("z").unwrap();
 
-    let mut summary_nodes = Vec::new();
-    if let Some(items) = block_entity
-        .list(nbt_utils::NBT_KEY_ITEMS)
-        .and_then(|l| l.compounds())
-    {
-        for item in items {
-            collect_summary_node(&item, cli_args, item_queries, &mut summary_nodes, counter);
-        }
+    if !item_queries.matches_holder(&id, block_entity) {
+        return;
     }
 
-    for single_item_field in &["item", "RecordItem", "Book"] {
-        if let Some(item) = block_entity.compound(single_item_field) {
-            collect_summary_node(&item, cli_args, item_queries, &mut summary_nodes, counter);
-        }
+    let location_str = format!("{x} {y} {z}");
+    let holder = Holder {
+        scope: &task.scope,
+        id: &id,
+        position: &location_str,
+        coordinates: Some([x, y, z].map(f64::from)),
+        player: None,
+    };
+    let mut summary_nodes = Vec::new();
+    for item in BLOCK_ENTITY_FIELDS.items(&id, block_entity) {
+        collect_summary_node(
+            &item,
+            &holder,
+            cli_args,
+            item_queries,
+            &mut summary_nodes,
+            output,
+        );
     }
 
-    let location_str = format!("{x} {y} {z}");
     print_per_source_summary_if_enabled(
         cli_args,
+        output,
         &task.scope.dimension,
         &id,
         &location_str,
@@ -655,81 +900,120 @@ fn process_block_entity(
     );
 }
 
-/// Recursively builds an `ItemSummaryNode` for `item_nbt` and all nested children (under `components -> minecraft:container` or `components -> minecraft:bundle_contents`),
-/// pushes leaves into `out_nodes`, and also updates the `global_counter`.
+/// Recursively builds an `ItemSummaryNode` for `item_nbt` and all nested children (found under the query's nested item paths),
+/// pushes leaves into `out_nodes`, and also updates the counter of `output`, or its
+/// occurrences with the `ndjson` format.
 fn collect_summary_node(
     item_nbt: &simdnbt::borrow::NbtCompound,
+    holder: &Holder,
     cli_args: &CliArgs,
-    queries: &[ItemFilter],
+    queries: &ItemQuery,
     out_nodes: &mut Vec<ItemSummaryNode>,
-    global_counter: &mut Counter,
+    output: &mut TaskOutput,
 ) {
-    let id = item_nbt.string(nbt_utils::NBT_KEY_ID).unwrap().to_string();
-    let count = item_nbt.int(nbt_utils::NBT_KEY_COUNT).unwrap_or(1) as u64;
+    collect_nested_summary_node(
+        item_nbt,
+        item_nbt.byte(nbt_utils::NBT_KEY_SLOT),
+        holder,
+        &mut Vec::new(),
+        None,
+        cli_args,
+        queries,
+        out_nodes,
+        output,
+    );
+}
 
-    let matches_filter = if queries.is_empty() {
-        true
-    } else {
-        let valence_nbt = convert_simdnbt_to_valence_nbt(item_nbt);
-        queries.iter().any(|q| {
-            let id_ok = q.id.as_ref().is_none_or(|qid| qid == &id);
-            let nbt_ok = q
-                .required_nbt
-                .as_ref()
-                .is_none_or(|req| nbt_is_subset(&valence_nbt, req));
-            id_ok && nbt_ok
-        })
+/// Same as `collect_summary_node` for an item nested inside the items of `parents`,
+/// outermost first. `parent_occurrence` is the index of the closest of them recorded in
+/// the occurrences of `output`. `slot` is where the item sits in its holder or parent.
+#[allow(clippy::too_many_arguments)]
+fn collect_nested_summary_node(
+    item_nbt: &simdnbt::borrow::NbtCompound,
+    slot: Option<i8>,
+    holder: &Holder,
+    parents: &mut Vec<String>,
+    parent_occurrence: Option<usize>,
+    cli_args: &CliArgs,
+    queries: &ItemQuery,
+    out_nodes: &mut Vec<ItemSummaryNode>,
+    output: &mut TaskOutput,
+) {
+    // Empty slots are saved as `{}` in some legacy lists
+    let Some(id) = item_nbt.string(nbt_utils::NBT_KEY_ID) else {
+        return;
     };
+    let id = id.to_string();
+    let count = item_nbt
+        .int(nbt_utils::NBT_KEY_COUNT)
+        .or_else(|| {
+            item_nbt
+                .byte(nbt_utils::NBT_KEY_LEGACY_COUNT)
+                .map(i32::from)
+        })
+        .unwrap_or(1) as u64;
 
-    let mut children = Vec::new();
+    let matches_filter = queries.matches(&ItemContext::new(&id, *item_nbt).with_parents(parents));
+    let depth = parents.len();
 
-    if let Some(components) = item_nbt.compound(nbt_utils::NBT_KEY_COMPONENTS) {
-        if let Some(nested_list) = components
-            .list(nbt_utils::NBT_KEY_MINECRAFT_CONTAINER)
-            .and_then(|l| l.compounds())
-        {
-            for nested_entry in nested_list {
-                if let Some(nested_item) = nested_entry.compound("item") {
-                    collect_summary_node(
-                        &nested_item,
-                        cli_args,
-                        queries,
-                        &mut children,
-                        global_counter,
-                    );
-                }
-            }
+    // Record the item before its contents, so nested occurrences can refer to it
+    let mut counted_components = None;
+    let mut occurrence = parent_occurrence;
+    if matches_filter {
+        let components = item_nbt.compound(nbt_utils::NBT_KEY_COMPONENTS);
+        counted_components = components
+            .as_ref()
+            .filter(|_| cli_args.counts_items() || cli_args.show_nbt)
+            .map(|components| output.components.intern(components));
+
+        if cli_args.collects_occurrences() {
+            occurrence = Some(output.occurrences.len());
+            output.occurrences.push(Occurrence::new(
+                holder,
+                slot,
+                parents,
+                parent_occurrence,
+                id.clone(),
+                count,
+                components.as_ref().map(convert_simdnbt_to_valence_nbt),
+            ));
         }
-
-        if let Some(nested_list) = components
-            .list(nbt_utils::NBT_KEY_MINECRAFT_BUNDLE_CONTENTS)
-            .and_then(|l| l.compounds())
+        if cli_args.counts_items() {
+            output.counter.add_key(
+                ItemKey::with_components(id.clone(), counted_components.clone())
+                    .with_depth(cli_args.group_by_depth.then_some(depth as u32)),
+                count,
+            );
+        }
+        if cli_args.counts_chunks()
+            && let Some(chunk) = output.current_chunk
         {
-            for nested_entry in nested_list {
-                collect_summary_node(
-                    &nested_entry,
-                    cli_args,
-                    queries,
-                    &mut children,
-                    global_counter,
-                );
-            }
+            *output.chunk_counts.entry(chunk).or_default() += count;
         }
     }
 
-    if matches_filter {
-        let nbt_components = item_nbt
-            .compound(nbt_utils::NBT_KEY_COMPONENTS)
-            .as_ref()
-            .map(convert_simdnbt_to_valence_nbt);
-
-        global_counter.add(id.clone(), nbt_components.as_ref(), count);
+    let mut children = Vec::new();
+    parents.push(id.clone());
+    for nested in queries.nested_paths.nested_items(*item_nbt) {
+        collect_nested_summary_node(
+            &nested.item,
+            nested.slot,
+            holder,
+            parents,
+            occurrence,
+            cli_args,
+            queries,
+            &mut children,
+            output,
+        );
+    }
+    parents.pop();
 
+    if matches_filter {
         let snbt = if cli_args.show_nbt {
-            nbt_components
-                .map(|c| valence_nbt::snbt::to_snbt_string(&c))
-                .as_deref()
-                .map(escape_nbt_string)
+            counted_components
+                .as_ref()
+                .map(|components| escape_nbt_string(components.snbt()))
         } else {
             None
         };
@@ -741,17 +1025,32 @@ fn collect_summary_node(
     }
 }
 
+/// How numeric tags are compared when matching NBT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumericMatching {
+    /// Tags must have the same type and value (`1b` ≠ `1`)
+    #[default]
+    Strict,
+    /// Integer tags (byte, short, int, long) compare by value, as do float and double tags
+    Lenient,
+}
+
 /// Returns `true` if `subset` is entirely contained within `superset`.
 /// Compounds require key-by-key subset checks; lists treat each element
 /// in `subset_list` as needing its own distinct match in `superset_list`.
 pub fn nbt_is_subset(superset: &Value, subset: &Value) -> bool {
+    nbt_is_subset_with(superset, subset, NumericMatching::Strict)
+}
+
+/// Same as [`nbt_is_subset`], comparing numeric tags according to `numeric`.
+pub fn nbt_is_subset_with(superset: &Value, subset: &Value, numeric: NumericMatching) -> bool {
     match (superset, subset) {
         // Compounds: every (key → sub_value) must match in sup_map
         (Value::Compound(sup_map), Value::Compound(sub_map)) => {
             sub_map.iter().all(|(field, sub_value)| {
                 sup_map
                     .get(field)
-                    .is_some_and(|sup_value| nbt_is_subset(sup_value, sub_value))
+                    .is_some_and(|sup_value| nbt_is_subset_with(sup_value, sub_value, numeric))
             })
         }
 
@@ -764,7 +1063,12 @@ pub fn nbt_is_subset(superset: &Value, subset: &Value) -> bool {
             subset_list.iter().all(|sub_element| {
                 // try to find an unused sup_element matching this sub_element
                 if let Some((idx, _)) = superset_list.iter().enumerate().find(|(i, sup_element)| {
-                    !used[*i] && nbt_is_subset(&sup_element.to_value(), &sub_element.to_value())
+                    !used[*i]
+                        && nbt_is_subset_with(
+                            &sup_element.to_value(),
+                            &sub_element.to_value(),
+                            numeric,
+                        )
                 }) {
                     used[idx] = true;
                     true
@@ -774,7 +1078,126 @@ pub fn nbt_is_subset(superset: &Value, subset: &Value) -> bool {
             })
         }
 
-        _ => superset == subset,
+        _ => nbt_values_equal(superset, subset, numeric),
+    }
+}
+
+/// Same as [`nbt_is_subset_with`] for a `superset` compound read by `simdnbt`, which is
+/// walked in place instead of being converted to a `valence_nbt::Value` first.
+pub fn nbt_compound_is_subset(
+    superset: &simdnbt::borrow::NbtCompound,
+    subset: &valence_nbt::Compound,
+    numeric: NumericMatching,
+) -> bool {
+    subset.iter().all(|(field, sub_value)| {
+        superset
+            .get(field)
+            .is_some_and(|tag| borrowed_is_subset(&BorrowedNbt::from_tag(&tag), sub_value, numeric))
+    })
+}
+
+/// A `simdnbt` value, with lists and compounds left unconverted.
+enum BorrowedNbt<'a, 'tape> {
+    Compound(simdnbt::borrow::NbtCompound<'a, 'tape>),
+    List(simdnbt::borrow::NbtList<'a, 'tape>),
+    Other(Option<Value>),
+}
+
+impl<'a, 'tape> BorrowedNbt<'a, 'tape> {
+    fn from_tag(tag: &simdnbt::borrow::NbtTag<'a, 'tape>) -> Self {
+        if let Some(compound) = tag.compound() {
+            BorrowedNbt::Compound(compound)
+        } else if let Some(list) = tag.list() {
+            BorrowedNbt::List(list)
+        } else {
+            BorrowedNbt::Other(nbt_utils::convert_tag(tag))
+        }
+    }
+
+    /// The elements of `list`, converting only those that are neither lists nor compounds.
+    fn elements(list: &simdnbt::borrow::NbtList<'a, 'tape>) -> Vec<Self> {
+        if let Some(compounds) = list.compounds() {
+            compounds.into_iter().map(BorrowedNbt::Compound).collect()
+        } else if let Some(lists) = list.lists() {
+            lists.into_iter().map(BorrowedNbt::List).collect()
+        } else {
+            nbt_utils::convert_list(list)
+                .iter()
+                .map(|element| BorrowedNbt::Other(Some(element.to_value())))
+                .collect()
+        }
+    }
+}
+
+fn borrowed_is_subset(superset: &BorrowedNbt, subset: &Value, numeric: NumericMatching) -> bool {
+    match (superset, subset) {
+        (BorrowedNbt::Compound(sup_compound), Value::Compound(sub_compound)) => {
+            nbt_compound_is_subset(sup_compound, sub_compound, numeric)
+        }
+        (BorrowedNbt::List(sup_list), Value::List(sub_list)) => {
+            let superset_list = BorrowedNbt::elements(sup_list);
+            let mut used = vec![false; superset_list.len()];
+            sub_list.iter().all(|sub_element| {
+                let sub_element = sub_element.to_value();
+                if let Some(idx) = (0..superset_list.len()).find(|&i| {
+                    !used[i] && borrowed_is_subset(&superset_list[i], &sub_element, numeric)
+                }) {
+                    used[idx] = true;
+                    true
+                } else {
+                    false
+                }
+            })
+        }
+        (BorrowedNbt::Other(Some(value)), _) => nbt_values_equal(value, subset, numeric),
+        _ => false,
+    }
+}
+
+/// Compares two values for equality, comparing numeric tags according to `numeric`.
+pub fn nbt_values_equal(a: &Value, b: &Value, numeric: NumericMatching) -> bool {
+    if numeric == NumericMatching::Lenient {
+        match (a, b) {
+            (
+                Value::Byte(_) | Value::Short(_) | Value::Int(_) | Value::Long(_),
+                Value::Byte(_) | Value::Short(_) | Value::Int(_) | Value::Long(_),
+            ) => return nbt_integer_value(a) == nbt_integer_value(b),
+            // A float widened to f64 is not equal to the same literal written as a double,
+            // so compare at float precision whenever a float is involved.
+            (Value::Float(x), Value::Double(y)) | (Value::Double(y), Value::Float(x)) => {
+                return *x == *y as f32;
+            }
+            _ => {}
+        }
+    }
+    a == b
+}
+
+/// Returns the value of a byte, short, int or long tag.
+pub fn nbt_integer_value(value: &Value) -> Option<i64> {
+    match value {
+        Value::Byte(v) => Some(*v as i64),
+        Value::Short(v) => Some(*v as i64),
+        Value::Int(v) => Some(*v as i64),
+        Value::Long(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// Returns `true` if `value` contains a numeric tag anywhere.
+pub fn nbt_contains_numbers(value: &Value) -> bool {
+    match value {
+        Value::Byte(_)
+        | Value::Short(_)
+        | Value::Int(_)
+        | Value::Long(_)
+        | Value::Float(_)
+        | Value::Double(_) => true,
+        Value::Compound(compound) => compound.values().any(nbt_contains_numbers),
+        Value::List(list) => list
+            .iter()
+            .any(|element| nbt_contains_numbers(&element.to_value())),
+        _ => false,
     }
 }
 
@@ -794,7 +1217,8 @@ pub fn escape_nbt_string(s: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::nbt_is_subset;
+    use super::{NumericMatching, nbt_compound_is_subset, nbt_is_subset, nbt_is_subset_with};
+    use crate::nbt_utils::snbt_to_binary;
     use valence_nbt::Value;
     use valence_nbt::snbt::from_snbt_str;
 
@@ -954,6 +1378,36 @@ mod tests {
         assert!(!nbt_is_subset(&sup, &sub));
     }
 
+    #[test]
+    fn lenient_integer_types_match_by_value() {
+        let sup = parse("{\"minecraft:mending\":1, flags:[1b, 2b]}");
+        let sub = parse("{\"minecraft:mending\":1b, flags:[2L]}");
+        assert!(!nbt_is_subset(&sup, &sub));
+        assert!(nbt_is_subset_with(&sup, &sub, NumericMatching::Lenient));
+
+        let wrong_value = parse("{\"minecraft:mending\":2s}");
+        assert!(!nbt_is_subset_with(
+            &sup,
+            &wrong_value,
+            NumericMatching::Lenient
+        ));
+    }
+
+    #[test]
+    fn lenient_float_and_double_match_by_value() {
+        let sup = parse("{val:0.1f}");
+        let sub = parse("{val:0.1d}");
+        assert!(!nbt_is_subset(&sup, &sub));
+        assert!(nbt_is_subset_with(&sup, &sub, NumericMatching::Lenient));
+    }
+
+    #[test]
+    fn lenient_does_not_mix_integers_and_floats() {
+        let sup = parse("{val:1}");
+        let sub = parse("{val:1.0f}");
+        assert!(!nbt_is_subset_with(&sup, &sub, NumericMatching::Lenient));
+    }
+
     #[test]
     fn compound_with_empty_list_and_nested_empty_compound() {
         let sup = parse("{data:{items:[], meta:{}}}");
@@ -967,4 +1421,38 @@ mod tests {
         let sub = parse("{msg:\"こんにちは\"}");
         assert!(nbt_is_subset(&sup, &sub));
     }
+
+    #[test]
+    fn unconverted_subset_agrees_with_converted() {
+        let cases = [
+            ("{a:1, b:{c:\"x\"}}", "{b:{c:\"x\"}}"),
+            ("{a:1, b:{c:\"x\"}}", "{b:{c:\"y\"}}"),
+            ("{a:1}", "{a:{}}"),
+            ("{list:[{id:1},{id:2},{id:1}]}", "{list:[{id:1},{id:1}]}"),
+            ("{list:[{id:1},{id:2}]}", "{list:[{id:1},{id:1}]}"),
+            ("{list:[[1,2],[3]]}", "{list:[[3]]}"),
+            ("{flags:[1b, 2b], val:0.1f}", "{flags:[2L], val:0.1d}"),
+            ("{ids:[I;1,2]}", "{ids:[I;1,2]}"),
+            ("{ids:[I;1,2]}", "{ids:[I;1]}"),
+            ("{data:{items:[], meta:{}}}", "{data:{items:[]}}"),
+            ("{val:1}", "{val:1.0f}"),
+            ("{msg:\"こんにちは\"}", "{msg:\"こんにちは\"}"),
+        ];
+        for (sup, sub) in cases {
+            let bytes = snbt_to_binary(sup);
+            let nbt = simdnbt::borrow::read(&mut std::io::Cursor::new(&bytes[..]))
+                .unwrap()
+                .unwrap();
+            let Value::Compound(sub_compound) = parse(sub) else {
+                unreachable!()
+            };
+            for numeric in [NumericMatching::Strict, NumericMatching::Lenient] {
+                assert_eq!(
+                    nbt_compound_is_subset(&nbt.as_compound(), &sub_compound, numeric),
+                    nbt_is_subset_with(&parse(sup), &parse(sub), numeric),
+                    "{sup} ⊇ {sub} ({numeric:?})"
+                );
+            }
+        }
+    }
 }
@@ -1,10 +1,23 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use nbt_sniffer::{
-    DataType, ScanTask, Scope,
-    cli::{CliArgs, OutputFormat, ViewMode, parse_item_args},
-    counter::CounterMap,
-    extract_single_player_uuid_from_level_dat, list_mca_files, process_task,
-    view::{aggregation::IsEmpty, view_by_id, view_by_nbt, view_detailed},
+    DataType, ScanResult, ScanTask, Scope,
+    cli::{CliArgs, DiffArgs, HistoryArgs, OutputFormat, ViewMode},
+    counter::Counter,
+    diff::{diff_counts, diff_locations, filter_rows, normalize_overworld},
+    extract_single_player_uuid_from_level_dat,
+    filter::ItemQuery,
+    heatmap,
+    history::{FileResultCache, FileSignature, history_rows, list_backups},
+    list_mca_files,
+    occurrences::{NdjsonSink, OccurrenceSink, OccurrenceWriter},
+    scan_task,
+    snapshot::Snapshot,
+    sqlite_export::SqliteSink,
+    tags::{TagRegistry, resolve_item_filter_tags},
+    view::{
+        aggregation::IsEmpty, view_by_enchantment, view_by_id, view_by_nbt, view_detailed,
+        view_diff, view_history, view_trades,
+    },
 };
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::Deserialize;
@@ -12,7 +25,7 @@ use std::{
     collections::HashMap,
     fs,
     path::{Path, PathBuf},
-    time::Instant,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use walkdir::WalkDir;
 
@@ -45,16 +58,128 @@ fn load_user_cache(world_root: &Path, cli_args: &CliArgs) -> HashMap<String, Str
 }
 
 fn main() {
-    let args = CliArgs::parse();
-    let queries = if args.all {
-        Vec::new()
+    let mut args = CliArgs::parse();
+    let mut queries = match ItemQuery::from_cli_args(&args) {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(diff) = args.diff_args() {
+        if let Err(e) = run_diff(diff, &mut queries, &args) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(history) = args.history_args() {
+        if let Err(e) = run_history(history, &mut queries, &args) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let start = Instant::now();
+    let mut result = if let Some(path) = args.from_snapshot.clone() {
+        match load_snapshot(&path, &mut args) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
     } else {
-        parse_item_args(&args.items)
+        let world_root = args.world_path.clone().unwrap_or_default();
+        let Some(result) = scan_world(&world_root, &mut queries, &args) else {
+            return;
+        };
+        result
     };
 
-    let world_root = args.world_path.clone();
-    let dimension_roots = get_all_dimension_roots(&world_root);
-    let user_cache = load_user_cache(&world_root, &args);
+    if let Some(path) = &args.save_snapshot {
+        save_snapshot(path, &result, &args);
+    }
+    if args.streams_ndjson() {
+        if args.from_snapshot.is_some() {
+            write_snapshot_occurrences(&result);
+        }
+        if args.verbose {
+            eprintln!("Scan completed in {:?}", start.elapsed());
+        }
+        return;
+    }
+
+    if args.trades {
+        let trades = &mut result.trades;
+        if trades.is_empty() {
+            eprintln!("No trade offers matched your query.");
+        }
+        view_trades(trades, &args);
+        if args.format == OutputFormat::Table && !trades.is_empty() {
+            println!("\nTotal offers matched: {}", trades.len());
+            println!("Scan completed in {:?}", start.elapsed());
+        }
+        return;
+    }
+
+    if result.counter_map.is_empty() {
+        if queries.is_match_all() {
+            eprintln!(
+                "No items found during scan. The world might be empty or data files unreadable."
+            );
+        } else {
+            eprintln!("No items matched your query.");
+            if queries.has_lenient_only_match() {
+                eprintln!(
+                    "Warning: some items would match if numeric types were ignored (e.g. 1b vs 1). Try --lenient-numbers."
+                );
+            }
+        }
+    }
+
+    // Trees are collected from parallel tasks in no particular order
+    result
+        .source_trees
+        .sort_by_cached_key(|tree| tree.to_string());
+    match args.view {
+        ViewMode::Detailed => view_detailed(&result, &args),
+        ViewMode::ById => view_by_id(&result, &args),
+        ViewMode::ByNbt => view_by_nbt(&result, &args),
+        ViewMode::ByEnchantment => view_by_enchantment(&result, &args),
+    }
+
+    if args.format == OutputFormat::Table && !result.counter_map.is_empty() {
+        println!(
+            "\nTotal items matched: {}",
+            result.counter_map.combined().total()
+        );
+        println!("Scan completed in {:?}", start.elapsed());
+    }
+}
+
+/// Expands the item tags used by the filters, from the data packs of the world at
+/// `world_root` or `--vanilla-data`.
+fn resolve_tags(world_root: &Path, queries: &mut ItemQuery, args: &CliArgs) {
+    let tag_registry = TagRegistry::load(world_root, args.vanilla_data.as_deref(), args.verbose);
+    if tag_registry.is_empty() {
+        eprintln!(
+            "Warning: no item tag files found. Use --vanilla-data to point at a vanilla data directory or client .jar."
+        );
+    }
+    resolve_item_filter_tags(queries, &tag_registry, args.verbose);
+}
+
+/// Lists the files to scan in the world at `world_root`, with the player names of its user
+/// cache. Returns `None` when there is nothing to scan.
+fn prepare_scan_tasks(
+    world_root: &Path,
+    args: &CliArgs,
+) -> Option<(Vec<ScanTask>, HashMap<String, String>)> {
+    let dimension_roots = get_all_dimension_roots(world_root);
+    let user_cache = load_user_cache(world_root, args);
 
     if dimension_roots.is_empty() && args.verbose {
         eprintln!(
@@ -63,8 +188,8 @@ fn main() {
         );
     }
 
-    let mut tasks = create_mca_scan_tasks(&dimension_roots, &args);
-    let player_tasks = create_player_scan_tasks(&world_root, &dimension_roots, &args);
+    let mut tasks = create_mca_scan_tasks(&dimension_roots, args);
+    let player_tasks = create_player_scan_tasks(world_root, &dimension_roots, args);
     tasks.extend(player_tasks);
 
     if tasks.is_empty() {
@@ -72,43 +197,360 @@ fn main() {
             "No scannable data (region/entities files, player data, or level.dat) found in {}. Nothing to do.",
             world_root.display()
         );
-        return;
+        return None;
     }
 
     if args.verbose {
         eprintln!("Total scan tasks created: {}", tasks.len());
     }
+    Some((tasks, user_cache))
+}
 
-    let start = Instant::now();
-    let counter_map = tasks
-        .into_par_iter()
-        .map(|task| process_task(task, &queries, &args, &user_cache))
-        .reduce(CounterMap::new, |mut a, b| {
-            for (scope, counter) in b.iter() {
-                a.merge_scope(scope.clone(), counter);
-            }
-            a
-        });
+/// Scans the world at `world_root`, streaming matched items to the occurrence sinks and
+/// writing heatmaps. Returns `None` when there is nothing to scan.
+fn scan_world(world_root: &Path, queries: &mut ItemQuery, args: &CliArgs) -> Option<ScanResult> {
+    if queries.uses_tags() {
+        resolve_tags(world_root, queries, args);
+    }
+    let (tasks, user_cache) = prepare_scan_tasks(world_root, args)?;
+
+    let occurrence_writer = match create_occurrence_writer(args) {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let queries = &*queries;
+    let scan = |tasks: Vec<(usize, ScanTask)>| {
+        tasks
+            .into_par_iter()
+            .map(|(index, task)| {
+                if occurrence_writer
+                    .as_ref()
+                    .is_some_and(OccurrenceWriter::has_failed)
+                {
+                    return ScanResult::new();
+                }
+                let mut output = scan_task(&task, queries, args, &user_cache);
+                if let Some(writer) = &occurrence_writer {
+                    // Snapshots keep their own copy of the item locations
+                    let occurrences = if args.snapshot_locations {
+                        output.occurrences.clone()
+                    } else {
+                        std::mem::take(&mut output.occurrences)
+                    };
+                    writer.send(index, occurrences);
+                }
+                ScanResult::from_task_output(task.scope, output)
+            })
+            .reduce(ScanResult::new, ScanResult::merge)
+    };
+    let mut tasks: Vec<_> = tasks.into_iter().enumerate().collect();
+    let result = if occurrence_writer.is_some() {
+        // Streamed occurrences are written in task order, so tasks run a window at a time
+        // rather than letting batches pile up behind a slow one. Waiting happens here, as
+        // blocking inside the thread pool could starve the task everyone waits for.
+        let mut result = ScanResult::new();
+        while !tasks.is_empty()
+            && !occurrence_writer
+                .as_ref()
+                .is_some_and(OccurrenceWriter::has_failed)
+        {
+            let rest = tasks.split_off(tasks.len().min(OccurrenceWriter::window()));
+            result = result.merge(scan(std::mem::replace(&mut tasks, rest)));
+        }
+        result
+    } else {
+        scan(tasks)
+    };
+
+    if let Some(writer) = occurrence_writer {
+        finish_occurrence_writer(writer, queries, args);
+    }
+    if let Some(dir) = &args.heatmap {
+        write_heatmaps(dir, &result, args);
+    }
+    Some(result)
+}
+
+/// Loads one side of a diff: a snapshot file, or a world directory to scan.
+fn load_diff_side(
+    path: &Path,
+    queries: &mut ItemQuery,
+    args: &CliArgs,
+) -> Result<ScanResult, String> {
+    if path.is_dir() {
+        return scan_world(path, queries, args)
+            .map(|result| normalize_overworld(result, path))
+            .ok_or_else(|| format!("nothing to scan in '{}'", path.display()));
+    }
+    let snapshot = Snapshot::load(path)?;
+    if args.diff_args().is_some_and(|diff| diff.by_location) && snapshot.occurrences.is_none() {
+        return Err(format!(
+            "snapshot '{}' has no item locations, save it with --snapshot-locations to compare by location",
+            path.display()
+        ));
+    }
+    if !queries.is_match_all() {
+        eprintln!(
+            "Warning: item filters are not applied to snapshot '{}'.",
+            path.display()
+        );
+    }
+    let world_path = snapshot.world_path.clone();
+    Ok(normalize_overworld(
+        snapshot.into_scan_result(false),
+        &world_path,
+    ))
+}
 
-    if counter_map.is_empty() {
-        if queries.is_empty() || args.all {
+/// Rejects the options that do not apply to `command`, and the output formats it does not
+/// support.
+fn check_command_args(command: &str, args: &CliArgs) -> Result<(), String> {
+    let incompatible = [
+        (args.world_path.is_some(), "--world-path"),
+        (args.from_snapshot.is_some(), "--from-snapshot"),
+        (args.save_snapshot.is_some(), "--save-snapshot"),
+        (args.export_sqlite.is_some(), "--export-sqlite"),
+        (args.heatmap.is_some(), "--heatmap"),
+        (args.trades, "--trades"),
+        (args.per_source_summary, "--per-source-summary"),
+    ];
+    if let Some((_, flag)) = incompatible.iter().find(|(used, _)| *used) {
+        return Err(format!("{flag} cannot be used with {command}"));
+    }
+    if !matches!(
+        args.format,
+        OutputFormat::Table
+            | OutputFormat::Json
+            | OutputFormat::PrettyJson
+            | OutputFormat::Csv
+            | OutputFormat::Tsv
+    ) {
+        let format = args
+            .format
+            .to_possible_value()
+            .map(|v| v.get_name().to_string());
+        return Err(format!(
+            "{command} does not support the {} format",
+            format.unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+fn run_diff(diff: &DiffArgs, queries: &mut ItemQuery, args: &CliArgs) -> Result<(), String> {
+    check_command_args("diff", args)?;
+
+    let old = load_diff_side(&diff.old, queries, args)?;
+    let new = load_diff_side(&diff.new, queries, args)?;
+    let with_nbt = args.view == ViewMode::Detailed;
+    let rows = if diff.by_location {
+        diff_locations(&old.occurrences, &new.occurrences, with_nbt)
+    } else {
+        diff_counts(&old.counter_map, &new.counter_map, with_nbt)
+    };
+    let (rows, summary) = filter_rows(rows, diff);
+    view_diff(&rows, &summary, args);
+    Ok(())
+}
+
+/// Counts the matched items of every backup in the folder, reusing the counts of the files
+/// that did not change since the previous backup.
+fn run_history(
+    history: &HistoryArgs,
+    queries: &mut ItemQuery,
+    args: &CliArgs,
+) -> Result<(), String> {
+    check_command_args("history", args)?;
+    let backups = list_backups(&history.backups)?;
+    if backups.is_empty() {
+        return Err(format!(
+            "no world directories or archives found in '{}'",
+            history.backups.display()
+        ));
+    }
+
+    let mut cache = FileResultCache::default();
+    // Kept open while the next backup is compared against the files it cached
+    let mut _previous_backup = None;
+    let mut points = Vec::with_capacity(backups.len());
+    for (index, backup) in backups.into_iter().enumerate() {
+        if args.verbose {
+            eprintln!("Scanning backup {}", backup.name);
+        }
+        let opened = backup.open()?;
+        let world_root = &opened.world_root;
+        // Tags are resolved once, from the oldest backup
+        if index == 0 && queries.uses_tags() {
+            resolve_tags(world_root, queries, args);
+        }
+        let Some((tasks, user_cache)) = prepare_scan_tasks(world_root, args) else {
+            points.push((backup, Counter::new()));
+            continue;
+        };
+
+        let queries = &*queries;
+        let previous = &cache;
+        let file_results: Vec<_> = tasks
+            .into_par_iter()
+            .map(|task| {
+                let relative_path = task
+                    .path
+                    .strip_prefix(world_root)
+                    .unwrap_or(&task.path)
+                    .to_path_buf();
+                let signature = FileSignature::of(&task.path);
+                if let Some(counter) = signature
+                    .and_then(|signature| previous.get(&relative_path, &task.path, signature))
+                {
+                    return (relative_path, task.path, signature, counter.clone(), true);
+                }
+                let output = scan_task(&task, queries, args, &user_cache);
+                let counter = ScanResult::from_task_output(task.scope, output)
+                    .counter_map
+                    .combined();
+                (relative_path, task.path, signature, counter, false)
+            })
+            .collect();
+
+        let mut next_cache = FileResultCache::default();
+        let mut total = Counter::new();
+        let mut reused = 0;
+        let file_count = file_results.len();
+        for (relative_path, path, signature, counter, was_reused) in file_results {
+            total.merge(&counter);
+            reused += usize::from(was_reused);
+            if let Some(signature) = signature {
+                next_cache.insert(relative_path, path, signature, counter);
+            }
+        }
+        if args.verbose {
             eprintln!(
-                "No items found during scan. The world might be empty or data files unreadable."
+                "Reused the counts of {reused} of {file_count} files, unchanged since the previous backup"
             );
-        } else {
-            eprintln!("No items matched your query.");
         }
+        cache = next_cache;
+        _previous_backup = Some(opened);
+        points.push((backup, total));
     }
 
-    match args.view {
-        ViewMode::Detailed => view_detailed(&counter_map, &args),
-        ViewMode::ById => view_by_id(&counter_map, &args),
-        ViewMode::ByNbt => view_by_nbt(&counter_map, &args),
+    let rows = history_rows(&points, args.view == ViewMode::Detailed);
+    view_history(&rows, points.len(), args);
+    Ok(())
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn save_snapshot(path: &Path, result: &ScanResult, args: &CliArgs) {
+    let snapshot = Snapshot::new(
+        args.world_path.clone().unwrap_or_default(),
+        unix_timestamp(),
+        args.group_by_depth,
+        &result.counter_map,
+        args.snapshot_locations
+            .then_some(result.occurrences.as_slice()),
+    );
+    match snapshot.save(path) {
+        Ok(()) => eprintln!("Saved snapshot to {}", path.display()),
+        Err(e) => eprintln!("Error: {e}"),
     }
+}
 
-    if args.format == OutputFormat::Table && !counter_map.is_empty() {
-        println!("\nTotal items matched: {}", counter_map.combined().total());
-        println!("Scan completed in {:?}", start.elapsed());
+/// Reads the scan result saved in a snapshot, taking the world path from it.
+fn load_snapshot(path: &Path, args: &mut CliArgs) -> Result<ScanResult, String> {
+    let snapshot = Snapshot::load(path)?;
+    if args.streams_ndjson() && snapshot.occurrences.is_none() {
+        return Err(format!(
+            "snapshot '{}' has no item locations, save it with --snapshot-locations to use the ndjson format",
+            path.display()
+        ));
+    }
+    if args.group_by_depth && !snapshot.group_by_depth {
+        eprintln!("Warning: the snapshot was saved without --group-by-depth, depths are unknown.");
+    }
+    args.world_path = Some(snapshot.world_path.clone());
+    Ok(snapshot.into_scan_result(args.group_by_depth))
+}
+
+fn write_snapshot_occurrences(result: &ScanResult) {
+    let mut sink = NdjsonSink::new(std::io::stdout());
+    match sink
+        .write_batch(&result.occurrences)
+        .and_then(|_| sink.finish())
+    {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+        Err(e) => eprintln!("Error writing matched items: {e}"),
+    }
+}
+
+/// Opens the destinations of individual matched items: stdout for the `ndjson` format and
+/// the `--export-sqlite` database.
+fn create_occurrence_writer(args: &CliArgs) -> Result<Option<OccurrenceWriter>, String> {
+    let mut sinks: Vec<Box<dyn OccurrenceSink>> = Vec::new();
+    if args.streams_ndjson() {
+        sinks.push(Box::new(NdjsonSink::new(std::io::stdout())));
+    }
+    if let Some(path) = &args.export_sqlite {
+        let metadata = [
+            ("tool_version", env!("CARGO_PKG_VERSION").to_string()),
+            (
+                "world_path",
+                args.world_path
+                    .clone()
+                    .unwrap_or_default()
+                    .display()
+                    .to_string(),
+            ),
+            ("scanned_at", unix_timestamp().to_string()),
+            (
+                "command_line",
+                std::env::args().collect::<Vec<_>>().join(" "),
+            ),
+        ];
+        sinks.push(Box::new(SqliteSink::create(path, &metadata)?));
+    }
+    Ok((!sinks.is_empty()).then(|| OccurrenceWriter::new(sinks)))
+}
+
+fn finish_occurrence_writer(writer: OccurrenceWriter, queries: &ItemQuery, args: &CliArgs) {
+    match writer.finish() {
+        Ok(written) => {
+            if written == 0 && args.streams_ndjson() && !queries.is_match_all() {
+                eprintln!("No items matched your query.");
+            }
+            if let Some(path) = &args.export_sqlite {
+                eprintln!("Exported {written} item stacks to {}", path.display());
+            }
+        }
+        // The reader went away, e.g. `| head`, and the scan stopped: nothing is left to do
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+        Err(e) => {
+            eprintln!("Error writing matched items: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn write_heatmaps(dir: &Path, result: &ScanResult, args: &CliArgs) {
+    match heatmap::write_heatmaps(dir, &result.chunk_counts, args.heatmap_resolution) {
+        Ok(paths) if paths.is_empty() => {
+            eprintln!("No matched items in region files, no heatmap written.")
+        }
+        Ok(paths) => {
+            for path in paths {
+                eprintln!("Wrote heatmap {}", path.display());
+            }
+        }
+        Err(e) => eprintln!("Error: {e}"),
     }
 }
 
@@ -0,0 +1,287 @@
+use std::fmt;
+
+use simdnbt::borrow::{NbtCompound, NbtList};
+use valence_nbt::{Value, value::ValueRef};
+
+/// One step of an `NbtPath`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Compound key, e.g. `components` or `"minecraft:custom_data"`
+    Key(String),
+    /// List element at a fixed index, e.g. `[0]`
+    Index(usize),
+    /// Every element of a list, `[]`
+    AnyElement,
+    /// Every value of a compound, `*`
+    AnyKey,
+}
+
+/// A path into an NBT tree, such as `components."minecraft:custom_data".owner`
+/// or `components."minecraft:lore"[]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NbtPath {
+    pub segments: Vec<PathSegment>,
+}
+
+impl NbtPath {
+    /// Parses a dotted path. Keys containing dots, colons or other special characters
+    /// must be double-quoted; `[N]` selects a list element and `[]` selects all of them,
+    /// while an unquoted `*` selects every value of a compound.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut chars = input.chars().peekable();
+        let mut expect_key = true;
+
+        while let Some(&c) = chars.peek() {
+            match c {
+                '.' if !expect_key => {
+                    chars.next();
+                    expect_key = true;
+                }
+                '[' => {
+                    chars.next();
+                    let mut index = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some(d) if d.is_ascii_digit() => index.push(d),
+                            Some(other) => {
+                                return Err(format!("unexpected '{other}' in list index"));
+                            }
+                            None => return Err("unterminated '['".to_string()),
+                        }
+                    }
+                    segments.push(if index.is_empty() {
+                        PathSegment::AnyElement
+                    } else {
+                        PathSegment::Index(index.parse().map_err(|e| format!("{e}"))?)
+                    });
+                    expect_key = false;
+                }
+                '"' if expect_key => {
+                    chars.next();
+                    let mut key = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\\') => match chars.next() {
+                                Some(escaped) => key.push(escaped),
+                                None => return Err("unterminated quoted key".to_string()),
+                            },
+                            Some('"') => break,
+                            Some(ch) => key.push(ch),
+                            None => return Err("unterminated quoted key".to_string()),
+                        }
+                    }
+                    segments.push(PathSegment::Key(key));
+                    expect_key = false;
+                }
+                _ if expect_key => {
+                    let mut key = String::new();
+                    while let Some(&ch) = chars.peek() {
+                        if ch == '.' || ch == '[' || ch == '"' {
+                            break;
+                        }
+                        key.push(ch);
+                        chars.next();
+                    }
+                    if key.is_empty() {
+                        return Err(format!("empty key in path '{input}'"));
+                    }
+                    segments.push(if key == "*" {
+                        PathSegment::AnyKey
+                    } else {
+                        PathSegment::Key(key)
+                    });
+                    expect_key = false;
+                }
+                other => return Err(format!("unexpected '{other}' in path '{input}'")),
+            }
+        }
+
+        if segments.is_empty() || expect_key {
+            return Err(format!("incomplete path '{input}'"));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Returns every value reached by this path. Paths through `[]` may reach several values;
+    /// a missing key, index or type mismatch simply yields nothing.
+    pub fn resolve<'a>(&self, root: &'a Value) -> Vec<ValueRef<'a>> {
+        let mut current = vec![root.as_value_ref()];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for value in current {
+                match (segment, value) {
+                    (PathSegment::Key(key), ValueRef::Compound(compound)) => {
+                        if let Some(child) = compound.get(key.as_str()) {
+                            next.push(child.as_value_ref());
+                        }
+                    }
+                    (PathSegment::Index(index), ValueRef::List(list)) => {
+                        if let Some(child) = list.get(*index) {
+                            next.push(child);
+                        }
+                    }
+                    (PathSegment::AnyElement, ValueRef::List(list)) => next.extend(list.iter()),
+                    (PathSegment::AnyKey, ValueRef::Compound(compound)) => {
+                        next.extend(compound.values().map(Value::as_value_ref));
+                    }
+                    _ => {}
+                }
+            }
+            if next.is_empty() {
+                return next;
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Returns every compound reached by this path in borrowed NBT, e.g. the item compounds
+    /// found under `components."minecraft:container"[].item`.
+    pub fn resolve_compounds<'a: 'tape, 'tape>(
+        &self,
+        root: NbtCompound<'a, 'tape>,
+    ) -> Vec<NbtCompound<'a, 'tape>> {
+        enum Node<'a, 'tape> {
+            Compound(NbtCompound<'a, 'tape>),
+            List(NbtList<'a, 'tape>),
+        }
+
+        let mut current = vec![Node::Compound(root)];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for node in current {
+                match (segment, node) {
+                    (PathSegment::Key(key), Node::Compound(compound)) => {
+                        if let Some(child) = compound.compound(key) {
+                            next.push(Node::Compound(child));
+                        } else if let Some(child) = compound.list(key) {
+                            next.push(Node::List(child));
+                        }
+                    }
+                    (PathSegment::AnyKey, Node::Compound(compound)) => {
+                        for (_, tag) in compound.iter() {
+                            if let Some(child) = tag.compound() {
+                                next.push(Node::Compound(child));
+                            } else if let Some(child) = tag.list() {
+                                next.push(Node::List(child));
+                            }
+                        }
+                    }
+                    (PathSegment::Index(index), Node::List(list)) => {
+                        if let Some(child) = list.compounds().and_then(|c| c.get(*index)) {
+                            next.push(Node::Compound(child));
+                        } else if let Some(child) = list.lists().and_then(|l| l.get(*index)) {
+                            next.push(Node::List(child));
+                        }
+                    }
+                    (PathSegment::AnyElement, Node::List(list)) => {
+                        if let Some(children) = list.compounds() {
+                            next.extend(children.into_iter().map(Node::Compound));
+                        } else if let Some(children) = list.lists() {
+                            next.extend(children.into_iter().map(Node::List));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if next.is_empty() {
+                return Vec::new();
+            }
+            current = next;
+        }
+        current
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Compound(compound) => Some(compound),
+                Node::List(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for NbtPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) => {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                        write!(f, "{key}")?;
+                    } else {
+                        write!(f, "\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))?;
+                    }
+                }
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+                PathSegment::AnyElement => write!(f, "[]")?,
+                PathSegment::AnyKey => {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    write!(f, "*")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence_nbt::snbt::from_snbt_str;
+
+    #[test]
+    fn parse_quoted_keys_and_indices() {
+        let path = NbtPath::parse(r#"components."minecraft:lore"[0]"#).unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                PathSegment::Key("components".to_string()),
+                PathSegment::Key("minecraft:lore".to_string()),
+                PathSegment::Index(0),
+            ]
+        );
+        assert_eq!(path.to_string(), r#"components."minecraft:lore"[0]"#);
+
+        let any = NbtPath::parse("Items[].id").unwrap();
+        assert_eq!(any.segments[1], PathSegment::AnyElement);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_paths() {
+        assert!(NbtPath::parse("").is_err());
+        assert!(NbtPath::parse("a.").is_err());
+        assert!(NbtPath::parse("a..b").is_err());
+        assert!(NbtPath::parse("a[x]").is_err());
+        assert!(NbtPath::parse(r#"a."b"#).is_err());
+    }
+
+    #[test]
+    fn resolve_through_compounds_and_lists() {
+        let nbt = from_snbt_str(
+            r#"{components:{"minecraft:custom_data":{owner:"Alex"},"minecraft:lore":["a","b"]}}"#,
+        )
+        .unwrap();
+
+        let owner = NbtPath::parse(r#"components."minecraft:custom_data".owner"#).unwrap();
+        let resolved = owner.resolve(&nbt);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].to_value(), Value::String("Alex".to_string()));
+
+        let lore = NbtPath::parse(r#"components."minecraft:lore"[]"#).unwrap();
+        assert_eq!(lore.resolve(&nbt).len(), 2);
+
+        let second = NbtPath::parse(r#"components."minecraft:lore"[1]"#).unwrap();
+        assert_eq!(
+            second.resolve(&nbt)[0].to_value(),
+            Value::String("b".to_string())
+        );
+
+        let missing = NbtPath::parse(r#"components."minecraft:enchantments""#).unwrap();
+        assert!(missing.resolve(&nbt).is_empty());
+    }
+}
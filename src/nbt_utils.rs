@@ -1,63 +1,63 @@
-use simdnbt::borrow::{NbtCompound, NbtList};
+use std::{
+    fmt::{self, Write},
+    hash::{DefaultHasher, Hasher},
+};
+
+use simdnbt::borrow::{NbtCompound, NbtList, NbtTag};
 use valence_nbt::{Compound, List, Value};
 
 pub const NBT_KEY_ID: &str = "id";
 pub const NBT_KEY_COUNT: &str = "count";
+pub const NBT_KEY_LEGACY_COUNT: &str = "Count"; // Before 1.20.5
 pub const NBT_KEY_POS: &str = "Pos";
-pub const NBT_KEY_ITEMS: &str = "Items";
-pub const NBT_KEY_INVENTORY: &str = "Inventory";
-pub const NBT_KEY_ITEM: &str = "Item";
-pub const NBT_KEY_EQUIPMENT: &str = "equipment";
+pub const NBT_KEY_SLOT: &str = "Slot";
 pub const NBT_KEY_PASSENGERS: &str = "Passengers";
 pub const NBT_KEY_COMPONENTS: &str = "components";
-pub const NBT_KEY_MINECRAFT_CONTAINER: &str = "minecraft:container";
-pub const NBT_KEY_MINECRAFT_BUNDLE_CONTENTS: &str = "minecraft:bundle_contents";
-pub const NBT_KEY_ENDER_ITEMS: &str = "EnderItems";
 pub const NBT_KEY_PLAYER_DATA: &str = "Data"; // For level.dat
 pub const NBT_KEY_PLAYER: &str = "Player"; // For level.dat, nested under "Data"
 
 pub fn convert_simdnbt_to_valence_nbt(compound: &NbtCompound) -> Value {
     let mut valence_compound = Compound::new();
 
-    for (key, _) in compound.iter() {
-        let key_str = key.to_string_lossy().into_owned();
-
-        let valence_value = if let Some(b) = compound.byte(&key_str) {
-            Value::Byte(b)
-        } else if let Some(s) = compound.short(&key_str) {
-            Value::Short(s)
-        } else if let Some(i) = compound.int(&key_str) {
-            Value::Int(i)
-        } else if let Some(l) = compound.long(&key_str) {
-            Value::Long(l)
-        } else if let Some(f) = compound.float(&key_str) {
-            Value::Float(f)
-        } else if let Some(d) = compound.double(&key_str) {
-            Value::Double(d)
-        } else if let Some(arr) = compound.byte_array(&key_str) {
-            let vec_i8 = arr.iter().map(|&b| b as i8).collect();
-            Value::ByteArray(vec_i8)
-        } else if let Some(s) = compound.string(&key_str) {
-            Value::String(s.to_string_lossy().into_owned())
-        } else if let Some(list) = compound.list(&key_str) {
-            let valence_list = convert_list(&list);
-            Value::List(valence_list)
-        } else if let Some(c) = compound.compound(&key_str) {
-            convert_simdnbt_to_valence_nbt(&c)
-        } else if let Some(arr) = compound.int_array(&key_str) {
-            Value::IntArray(arr.to_vec())
-        } else if let Some(arr) = compound.long_array(&key_str) {
-            Value::LongArray(arr.to_vec())
-        } else {
-            continue;
-        };
-
-        valence_compound.insert(key_str, valence_value);
+    for (key, tag) in compound.iter() {
+        if let Some(valence_value) = convert_tag(&tag) {
+            valence_compound.insert(key.to_string_lossy().into_owned(), valence_value);
+        }
     }
 
     Value::Compound(valence_compound)
 }
 
+/// Converts a single tag, or returns `None` for an unknown tag type.
+pub fn convert_tag(tag: &NbtTag) -> Option<Value> {
+    let value = if let Some(b) = tag.byte() {
+        Value::Byte(b)
+    } else if let Some(s) = tag.short() {
+        Value::Short(s)
+    } else if let Some(i) = tag.int() {
+        Value::Int(i)
+    } else if let Some(l) = tag.long() {
+        Value::Long(l)
+    } else if let Some(f) = tag.float() {
+        Value::Float(f)
+    } else if let Some(d) = tag.double() {
+        Value::Double(d)
+    } else if let Some(arr) = tag.byte_array() {
+        Value::ByteArray(arr.iter().map(|&b| b as i8).collect())
+    } else if let Some(s) = tag.string() {
+        Value::String(s.to_string_lossy().into_owned())
+    } else if let Some(list) = tag.list() {
+        Value::List(convert_list(&list))
+    } else if let Some(c) = tag.compound() {
+        convert_simdnbt_to_valence_nbt(&c)
+    } else if let Some(arr) = tag.int_array() {
+        Value::IntArray(arr)
+    } else {
+        Value::LongArray(tag.long_array()?)
+    };
+    Some(value)
+}
+
 pub fn convert_list(list: &NbtList) -> List {
     let mut valence_list = List::new();
 
@@ -115,6 +115,201 @@ pub fn convert_list(list: &NbtList) -> List {
     valence_list
 }
 
+/// Writes `compound` as SNBT, exactly as `valence_nbt::snbt::to_snbt_string` would write
+/// its conversion by `convert_simdnbt_to_valence_nbt`, without building the converted tree.
+pub fn simdnbt_to_snbt(compound: &NbtCompound) -> String {
+    let mut output = String::new();
+    let _ = write_snbt_compound(&mut output, compound);
+    output
+}
+
+/// The `snbt_hash` of `simdnbt_to_snbt(compound)`, computed without writing the SNBT.
+pub fn simdnbt_snbt_hash(compound: &NbtCompound) -> u128 {
+    let mut hasher = SnbtHasher::new();
+    let _ = write_snbt_compound(&mut hasher, compound);
+    hasher.finish()
+}
+
+/// A 128-bit hash of `snbt`, so that distinct SNBT practically never share a hash.
+pub fn snbt_hash(snbt: &str) -> u128 {
+    let mut hasher = SnbtHasher::new();
+    let _ = hasher.write_str(snbt);
+    hasher.finish()
+}
+
+/// Hashes the text written to it twice, with differently seeded hashers. The result does
+/// not depend on how the text is split into writes.
+struct SnbtHasher {
+    low: DefaultHasher,
+    high: DefaultHasher,
+}
+
+impl SnbtHasher {
+    fn new() -> Self {
+        let mut high = DefaultHasher::new();
+        high.write_u8(0xff);
+        Self {
+            low: DefaultHasher::new(),
+            high,
+        }
+    }
+
+    fn finish(&self) -> u128 {
+        (u128::from(self.high.finish()) << 64) | u128::from(self.low.finish())
+    }
+}
+
+impl fmt::Write for SnbtHasher {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.low.write(s.as_bytes());
+        self.high.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+fn write_snbt_compound(output: &mut impl fmt::Write, compound: &NbtCompound) -> fmt::Result {
+    // Converted compounds are sorted by key, keep the last of duplicate keys and leave out
+    // unknown tag types
+    let mut entries: Vec<_> = compound
+        .iter()
+        .filter(|(_, tag)| (1..=12).contains(&tag.id()))
+        .map(|(key, tag)| (key.to_string_lossy(), tag))
+        .collect();
+    entries.reverse();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries.dedup_by(|(a, _), (b, _)| a == b);
+
+    output.write_char('{')?;
+    for (i, (key, tag)) in entries.iter().enumerate() {
+        if i > 0 {
+            output.write_char(',')?;
+        }
+        write_snbt_string(output, key)?;
+        output.write_char(':')?;
+        write_snbt_tag(output, tag)?;
+    }
+    output.write_char('}')
+}
+
+fn write_snbt_tag(output: &mut impl fmt::Write, tag: &NbtTag) -> fmt::Result {
+    if let Some(b) = tag.byte() {
+        write!(output, "{b}b")
+    } else if let Some(s) = tag.short() {
+        write!(output, "{s}s")
+    } else if let Some(i) = tag.int() {
+        write!(output, "{i}")
+    } else if let Some(l) = tag.long() {
+        write!(output, "{l}l")
+    } else if let Some(f) = tag.float() {
+        write!(output, "{f}f")
+    } else if let Some(d) = tag.double() {
+        write!(output, "{d}d")
+    } else if let Some(arr) = tag.byte_array() {
+        write_snbt_array(output, "B;", arr.iter().map(|&b| b as i8), "b")
+    } else if let Some(s) = tag.string() {
+        write_snbt_string(output, &s.to_string_lossy())
+    } else if let Some(list) = tag.list() {
+        write_snbt_list(output, &list)
+    } else if let Some(c) = tag.compound() {
+        write_snbt_compound(output, &c)
+    } else if let Some(arr) = tag.int_array() {
+        write_snbt_array(output, "I;", arr, "")
+    } else if let Some(arr) = tag.long_array() {
+        write_snbt_array(output, "L;", arr, "l")
+    } else {
+        Ok(())
+    }
+}
+
+fn write_snbt_list<W: fmt::Write>(output: &mut W, list: &NbtList) -> fmt::Result {
+    fn elements<W: fmt::Write, T>(
+        output: &mut W,
+        items: impl IntoIterator<Item = T>,
+        mut write: impl FnMut(&mut W, T) -> fmt::Result,
+    ) -> fmt::Result {
+        output.write_char('[')?;
+        for (i, item) in items.into_iter().enumerate() {
+            if i > 0 {
+                output.write_char(',')?;
+            }
+            write(output, item)?;
+        }
+        output.write_char(']')
+    }
+
+    // Arrays nested in lists get valence's list prefixes, without a `;`
+    if let Some(bytes) = list.bytes() {
+        elements(output, bytes, |o, b| write!(o, "{b}b"))
+    } else if let Some(shorts) = list.shorts() {
+        elements(output, shorts, |o, s| write!(o, "{s}s"))
+    } else if let Some(ints) = list.ints() {
+        elements(output, ints, |o, i| write!(o, "{i}"))
+    } else if let Some(longs) = list.longs() {
+        elements(output, longs, |o, l| write!(o, "{l}l"))
+    } else if let Some(floats) = list.floats() {
+        elements(output, floats, |o, f| write!(o, "{f}f"))
+    } else if let Some(doubles) = list.doubles() {
+        elements(output, doubles, |o, d| write!(o, "{d}d"))
+    } else if let Some(byte_arrays) = list.byte_arrays() {
+        elements(output, byte_arrays, |o, arr| {
+            write_snbt_array(o, "B", arr.iter().map(|&b| b as i8), "b")
+        })
+    } else if let Some(strings) = list.strings() {
+        elements(output, strings, |o, s| {
+            write_snbt_string(o, &s.to_string_lossy())
+        })
+    } else if let Some(lists) = list.lists() {
+        elements(output, lists, |o, l| write_snbt_list(o, &l))
+    } else if let Some(compounds) = list.compounds() {
+        elements(output, compounds, |o, c| write_snbt_compound(o, &c))
+    } else if let Some(int_arrays) = list.int_arrays() {
+        elements(output, int_arrays, |o, arr| {
+            write_snbt_array(o, "", arr.to_vec(), "")
+        })
+    } else if let Some(long_arrays) = list.long_arrays() {
+        elements(output, long_arrays, |o, arr| {
+            write_snbt_array(o, "L", arr.to_vec(), "l")
+        })
+    } else {
+        output.write_str("[]")
+    }
+}
+
+fn write_snbt_array<T: fmt::Display>(
+    output: &mut impl fmt::Write,
+    prefix: &str,
+    values: impl IntoIterator<Item = T>,
+    suffix: &str,
+) -> fmt::Result {
+    output.write_char('[')?;
+    output.write_str(prefix)?;
+    for (i, value) in values.into_iter().enumerate() {
+        if i > 0 {
+            output.write_char(',')?;
+        }
+        write!(output, "{value}{suffix}")?;
+    }
+    output.write_char(']')
+}
+
+fn write_snbt_string(output: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    let bare = s
+        .chars()
+        .all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' | '+' | '.'));
+    if bare {
+        return output.write_str(s);
+    }
+    output.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => output.write_str("\\\"")?,
+            '\\' => output.write_str("\\\\")?,
+            _ => output.write_char(c)?,
+        }
+    }
+    output.write_char('"')
+}
+
 /// Extracts a UUID string from an NBT compound.
 /// It checks for an Int Array named `UUID` (e.g. `[I;-132296786,2112623056,-1486552928,-920753162]`), which is the standard for modern Minecraft versions.
 pub fn get_uuid_from_nbt(nbt_compound: &NbtCompound) -> Option<String> {
@@ -133,11 +328,116 @@ pub fn get_uuid_from_nbt(nbt_compound: &NbtCompound) -> Option<String> {
     None
 }
 
-/// Helper to get a formatted string for an entity's position.
-pub fn get_entity_pos_string(entity_nbt: &simdnbt::borrow::NbtCompound) -> Option<String> {
+/// Reads the stack size of an item, from `count` or the `Count` byte used before 1.20.5.
+/// Defaults to 1 when absent.
+pub fn item_count(item_nbt: &NbtCompound) -> u64 {
+    item_nbt
+        .int(NBT_KEY_COUNT)
+        .or_else(|| item_nbt.byte(NBT_KEY_LEGACY_COUNT).map(i32::from))
+        .unwrap_or(1) as u64
+}
+
+/// Reads the `Pos` coordinates of an entity or player.
+pub fn get_entity_pos(entity_nbt: &simdnbt::borrow::NbtCompound) -> Option<[f64; 3]> {
     entity_nbt
         .list(NBT_KEY_POS)
         .and_then(|pos_list| pos_list.doubles())
         .filter(|doubles| doubles.len() >= 3)
-        .map(|doubles| format!("{:.2} {:.2} {:.2}", doubles[0], doubles[1], doubles[2]))
+        .map(|doubles| [doubles[0], doubles[1], doubles[2]])
+}
+
+/// Helper to get a formatted string for an entity's position.
+pub fn get_entity_pos_string(entity_nbt: &simdnbt::borrow::NbtCompound) -> Option<String> {
+    get_entity_pos(entity_nbt).map(|[x, y, z]| format!("{x:.2} {y:.2} {z:.2}"))
+}
+
+/// Converts NBT to plain JSON. Numeric types are lost, and arrays become lists of numbers.
+pub fn nbt_to_json(value: &Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+
+    match value {
+        Value::Byte(v) => Json::from(*v),
+        Value::Short(v) => Json::from(*v),
+        Value::Int(v) => Json::from(*v),
+        Value::Long(v) => Json::from(*v),
+        Value::Float(v) => Json::from(*v),
+        Value::Double(v) => Json::from(*v),
+        Value::String(v) => Json::from(v.as_str()),
+        Value::ByteArray(v) => Json::from(v.clone()),
+        Value::IntArray(v) => Json::from(v.clone()),
+        Value::LongArray(v) => Json::from(v.clone()),
+        Value::List(list) => Json::Array(
+            list.iter()
+                .map(|element| nbt_to_json(&element.to_value()))
+                .collect(),
+        ),
+        Value::Compound(compound) => Json::Object(
+            compound
+                .iter()
+                .map(|(key, value)| (key.to_string(), nbt_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Encodes an SNBT compound as uncompressed binary NBT, so tests can read it back with `simdnbt::borrow`.
+#[cfg(test)]
+pub(crate) fn snbt_to_binary(snbt: &str) -> Vec<u8> {
+    let Value::Compound(compound) = valence_nbt::snbt::from_snbt_str(snbt).unwrap() else {
+        panic!("test SNBT must be a compound");
+    };
+    let mut bytes = Vec::new();
+    valence_nbt::to_binary(&compound, &mut bytes, "").unwrap();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nbt_to_json_drops_numeric_types() {
+        let value = valence_nbt::snbt::from_snbt_str(
+            r#"{"minecraft:custom_data":{flag:1b,ratio:0.5f,ids:[I;1,2],names:["a","b"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            nbt_to_json(&value),
+            serde_json::json!({"minecraft:custom_data": {"flag": 1, "ratio": 0.5, "ids": [1, 2], "names": ["a", "b"]}})
+        );
+    }
+
+    #[test]
+    fn writes_the_same_snbt_as_the_converted_nbt() {
+        let bytes = snbt_to_binary(
+            r#"{zeta:1b,"minecraft:lore":["plain",'say "hi"'],a:{s:2s,l:-3l,f:0.1f,d:1.5d},arrays:{b:[B;1b,-2b],i:[I;3,4],l:[L;5l]},nested:[[I;1,2],[I;]],lists:[[1,2],[]],empty:[],bytes:[B;],"with space":"back\\slash"}"#,
+        );
+        let nbt = simdnbt::borrow::read(&mut std::io::Cursor::new(&bytes[..]))
+            .unwrap()
+            .unwrap();
+        let compound = nbt.as_compound();
+        let snbt = simdnbt_to_snbt(&compound);
+        assert_eq!(
+            snbt,
+            valence_nbt::snbt::to_snbt_string(&convert_simdnbt_to_valence_nbt(&compound))
+        );
+        assert_eq!(simdnbt_snbt_hash(&compound), snbt_hash(&snbt));
+        assert_ne!(snbt_hash(&snbt), snbt_hash("{}"));
+
+        // {a:1b,b:2b,a:3b}, which cannot be written from a converted compound
+        let duplicates = [
+            10, 0, 0, 1, 0, 1, b'a', 1, 1, 0, 1, b'b', 2, 1, 0, 1, b'a', 3, 0,
+        ];
+        let nbt = simdnbt::borrow::read(&mut std::io::Cursor::new(&duplicates[..]))
+            .unwrap()
+            .unwrap();
+        let compound = nbt.as_compound();
+        let snbt = simdnbt_to_snbt(&compound);
+        assert_eq!(snbt, "{a:3b,b:2b}");
+        assert_eq!(
+            snbt,
+            valence_nbt::snbt::to_snbt_string(&convert_simdnbt_to_valence_nbt(&compound))
+        );
+        assert_eq!(simdnbt_snbt_hash(&compound), snbt_hash(&snbt));
+    }
 }
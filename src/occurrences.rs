@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufWriter, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+    },
+    thread::{self, JoinHandle},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use valence_nbt::Value;
+
+use crate::{DataType, Scope};
+
+/// The block entity, entity or player holding an item.
+pub struct Holder<'a> {
+    pub scope: &'a Scope,
+    pub id: &'a str,
+    pub position: &'a str,
+    /// Block or entity coordinates, when known
+    pub coordinates: Option<[f64; 3]>,
+    /// Player name or UUID, for player data
+    pub player: Option<&'a str>,
+}
+
+/// A single matched item stack, as written by the `ndjson` output format and snapshots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Occurrence {
+    pub dimension: String,
+    pub data_type: DataType,
+    pub holder: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player: Option<String>,
+    pub position: String,
+    #[serde(skip)]
+    pub coordinates: Option<[f64; 3]>,
+    /// The `Slot` of the item, or the `slot` of the container entry holding it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<i8>,
+    /// IDs of the items this one is nested in, outermost first
+    pub path: Vec<String>,
+    /// Index, among the occurrences of the same scan task, of the closest enclosing item
+    /// that also matched
+    #[serde(skip)]
+    pub parent: Option<usize>,
+    pub id: String,
+    pub count: u64,
+    /// Item components, serialized as SNBT
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_snbt",
+        deserialize_with = "deserialize_snbt"
+    )]
+    pub components: Option<Value>,
+}
+
+fn serialize_snbt<S: Serializer>(value: &Option<Value>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_str(&valence_nbt::snbt::to_snbt_string(value)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_snbt<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|snbt| valence_nbt::snbt::from_snbt_str(&snbt).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Occurrence {
+    pub fn new(
+        holder: &Holder,
+        slot: Option<i8>,
+        path: &[String],
+        parent: Option<usize>,
+        id: String,
+        count: u64,
+        components: Option<Value>,
+    ) -> Self {
+        Self {
+            dimension: holder.scope.dimension.clone(),
+            data_type: holder.scope.data_type,
+            holder: holder.id.to_string(),
+            player: holder.player.map(str::to_string),
+            position: holder.position.to_string(),
+            coordinates: holder.coordinates,
+            slot,
+            path: path.to_vec(),
+            parent,
+            id,
+            count,
+            components,
+        }
+    }
+
+    /// Whether `self` and `other` were found in the same holder.
+    pub fn same_holder(&self, other: &Occurrence) -> bool {
+        self.dimension == other.dimension
+            && self.data_type == other.data_type
+            && self.holder == other.holder
+            && self.position == other.position
+            && self.player == other.player
+    }
+}
+
+/// A destination for the occurrences found by a scan, fed by `OccurrenceWriter`.
+pub trait OccurrenceSink: Send {
+    /// Writes the occurrences of one scan task. Their `parent` indices refer to `batch`.
+    fn write_batch(&mut self, batch: &[Occurrence]) -> io::Result<()>;
+
+    /// Called once after the last batch.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Writes occurrences as newline-delimited JSON.
+pub struct NdjsonSink<W: Write + Send> {
+    out: BufWriter<W>,
+}
+
+impl<W: Write + Send> NdjsonSink<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: BufWriter::new(out),
+        }
+    }
+}
+
+impl<W: Write + Send> OccurrenceSink for NdjsonSink<W> {
+    fn write_batch(&mut self, batch: &[Occurrence]) -> io::Result<()> {
+        for occurrence in batch {
+            serde_json::to_writer(&mut self.out, occurrence)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+type Batch = (usize, Vec<Occurrence>);
+
+/// Hands occurrences to a set of sinks from a dedicated thread.
+///
+/// Scan tasks send their occurrences as one batch per task index, from any thread. Batches
+/// are written in task order as soon as every earlier task has reported, so the output is
+/// the same across runs while memory only holds the batches that arrived early. Callers
+/// bound those by starting at most `window()` tasks past the last one that reported.
+pub struct OccurrenceWriter {
+    sender: SyncSender<Batch>,
+    handle: JoinHandle<io::Result<u64>>,
+    failed: Arc<AtomicBool>,
+}
+
+impl OccurrenceWriter {
+    pub fn new(sinks: Vec<Box<dyn OccurrenceSink>>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(rayon::current_num_threads() * 2);
+        let failed = Arc::new(AtomicBool::new(false));
+        let writer_failed = failed.clone();
+        let handle = thread::spawn(move || {
+            let result = write_batches(receiver, sinks);
+            writer_failed.store(result.is_err(), Ordering::Relaxed);
+            result
+        });
+        Self {
+            sender,
+            handle,
+            failed,
+        }
+    }
+
+    /// How many scan tasks to run at once, so that batches waiting for an earlier slow task
+    /// stay few.
+    pub fn window() -> usize {
+        rayon::current_num_threads() * 4
+    }
+
+    /// Whether writing failed, e.g. because the reader of the output went away, in which
+    /// case scanning further is pointless.
+    pub fn has_failed(&self) -> bool {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Queues the occurrences of task `index`. Every index from 0 must be sent once, even
+    /// with no occurrences, for later batches to be written before `finish`.
+    pub fn send(&self, index: usize, occurrences: Vec<Occurrence>) {
+        // The writer thread only hangs up after a write error, which `finish` reports
+        let _ = self.sender.send((index, occurrences));
+    }
+
+    /// Waits for every queued batch to be written, returning the number of occurrences.
+    pub fn finish(self) -> io::Result<u64> {
+        drop(self.sender);
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("occurrence writer panicked")))
+    }
+}
+
+fn write_batches(
+    receiver: Receiver<Batch>,
+    mut sinks: Vec<Box<dyn OccurrenceSink>>,
+) -> io::Result<u64> {
+    let mut pending = BTreeMap::new();
+    let mut next_index = 0;
+    let mut written = 0;
+
+    let mut write_batch = |batch: Vec<Occurrence>| -> io::Result<()> {
+        for sink in sinks.iter_mut() {
+            sink.write_batch(&batch)?;
+        }
+        written += batch.len() as u64;
+        Ok(())
+    };
+
+    for (index, batch) in receiver {
+        pending.insert(index, batch);
+        while let Some(batch) = pending.remove(&next_index) {
+            write_batch(batch)?;
+            next_index += 1;
+        }
+    }
+    // Batches left after a missing index, e.g. from a task that panicked
+    for batch in pending.into_values() {
+        write_batch(batch)?;
+    }
+
+    for sink in sinks.iter_mut() {
+        sink.finish()?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn occurrence(id: &str) -> Occurrence {
+        let scope = Scope {
+            dimension: "overworld".to_string(),
+            data_type: DataType::BlockEntity,
+        };
+        let holder = Holder {
+            scope: &scope,
+            id: "minecraft:chest",
+            position: "1 2 3",
+            coordinates: Some([1.0, 2.0, 3.0]),
+            player: None,
+        };
+        let components = valence_nbt::snbt::from_snbt_str(r#"{"minecraft:damage":3}"#).unwrap();
+        Occurrence::new(
+            &holder,
+            Some(4),
+            &[],
+            None,
+            id.to_string(),
+            1,
+            Some(components),
+        )
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_batches_in_task_order() {
+        let buffer = SharedBuffer::default();
+        let writer = OccurrenceWriter::new(vec![Box::new(NdjsonSink::new(buffer.clone()))]);
+        writer.send(2, vec![occurrence("minecraft:stone")]);
+        writer.send(0, vec![occurrence("minecraft:dirt")]);
+        writer.send(1, vec![]);
+        assert_eq!(writer.finish().unwrap(), 2);
+
+        let out = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["id"], "minecraft:dirt");
+        assert_eq!(lines[1]["id"], "minecraft:stone");
+        assert_eq!(lines[1]["holder"], "minecraft:chest");
+        assert_eq!(lines[1]["data_type"], "BlockEntity");
+        assert_eq!(lines[1]["slot"], 4);
+        assert_eq!(lines[1]["components"], r#"{"minecraft:damage":3}"#);
+    }
+
+    struct BrokenSink;
+
+    impl OccurrenceSink for BrokenSink {
+        fn write_batch(&mut self, _batch: &[Occurrence]) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reports_a_failed_writer_to_the_scan() {
+        let writer = OccurrenceWriter::new(vec![Box::new(BrokenSink)]);
+        assert!(!writer.has_failed());
+        writer.send(0, vec![occurrence("minecraft:dirt")]);
+        let start = std::time::Instant::now();
+        while !writer.has_failed() && start.elapsed() < std::time::Duration::from_secs(5) {
+            thread::yield_now();
+        }
+        assert!(writer.has_failed());
+        writer.send(1, vec![occurrence("minecraft:stone")]);
+        assert_eq!(
+            writer.finish().unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    TaskOutput, cli::CliArgs, counter::Counter, filter::ItemQuery, occurrences::Occurrence,
+};
+
+/// Bumped whenever a change to the cache layout or to the scan itself would make cached
+/// results wrong.
+pub const REGION_CACHE_FORMAT_VERSION: u32 = 1;
+
+const SECTOR_SIZE: usize = 4096;
+
+/// Hashes every setting that changes what a scan finds in a chunk: item filters with their
+/// resolved tags, depth and holder restrictions, nested item paths and what is collected.
+pub fn settings_fingerprint(queries: &ItemQuery, args: &CliArgs) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (REGION_CACHE_FORMAT_VERSION, env!("CARGO_PKG_VERSION")).hash(&mut hasher);
+    (
+        args.all,
+        &args.items,
+        &args.filters,
+        &args.excludes,
+        &args.name,
+        &args.lore_contains,
+        &args.enchantments,
+    )
+        .hash(&mut hasher);
+    (
+        args.min_depth,
+        args.max_depth,
+        &args.inside,
+        &args.in_containers,
+        &args.nested_item_paths,
+        args.no_default_nested_paths,
+        args.lenient_numbers,
+    )
+        .hash(&mut hasher);
+    (
+        args.min_inhabited_ticks,
+        args.group_by_depth,
+        args.counts_items(),
+        args.collects_occurrences(),
+    )
+        .hash(&mut hasher);
+    // Tags may expand differently once data packs change
+    queries.for_each_item_filter(|filter| {
+        if let Some(members) = &filter.tag_members {
+            let mut members: Vec<_> = members.iter().collect();
+            members.sort();
+            members.hash(&mut hasher);
+        }
+    });
+    hasher.finish()
+}
+
+/// The location and timestamp entries of a chunk in the region file header, which change
+/// whenever the game saves the chunk again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkStamp {
+    pub location: u32,
+    pub timestamp: u32,
+}
+
+impl ChunkStamp {
+    /// Reads the header entries of chunk `index` (`x + z * 32`). `None` when the header is
+    /// truncated.
+    pub fn from_header(region_data: &[u8], index: usize) -> Option<Self> {
+        let entry = |offset: usize| {
+            region_data
+                .get(offset + index * 4..offset + index * 4 + 4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        Some(Self {
+            location: entry(0)?,
+            timestamp: entry(SECTOR_SIZE)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedOccurrence {
+    #[serde(flatten)]
+    occurrence: Occurrence,
+    /// Index among the occurrences of the same chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coordinates: Option<[f64; 3]>,
+}
+
+/// What the scan of one chunk found.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedChunk {
+    stamp: ChunkStamp,
+    counter: Counter,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    occurrences: Vec<CachedOccurrence>,
+    /// Matched items, for heatmaps
+    #[serde(default)]
+    matched: u64,
+}
+
+impl CachedChunk {
+    pub fn stamp(&self) -> ChunkStamp {
+        self.stamp
+    }
+
+    /// Takes the results of a chunk scanned into its own `output`.
+    pub fn from_output(stamp: ChunkStamp, output: TaskOutput) -> Self {
+        Self {
+            stamp,
+            counter: output.counter,
+            occurrences: output
+                .occurrences
+                .into_iter()
+                .map(|occurrence| CachedOccurrence {
+                    parent: occurrence.parent,
+                    coordinates: occurrence.coordinates,
+                    occurrence,
+                })
+                .collect(),
+            matched: output.chunk_counts.values().sum(),
+        }
+    }
+
+    /// Adds the results of the chunk to the output of its scan task.
+    pub fn add_to(&self, output: &mut TaskOutput) {
+        output.counter.merge(&self.counter);
+        let offset = output.occurrences.len();
+        output
+            .occurrences
+            .extend(self.occurrences.iter().map(|cached| Occurrence {
+                parent: cached.parent.map(|parent| parent + offset),
+                coordinates: cached.coordinates,
+                ..cached.occurrence.clone()
+            }));
+        if self.matched > 0
+            && let Some(chunk) = output.current_chunk
+        {
+            *output.chunk_counts.entry(chunk).or_default() += self.matched;
+        }
+    }
+}
+
+/// The size and modification time of a file, in nanoseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified_nanos: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified_nanos: modified.as_nanos() as u64,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RegionCacheFile {
+    format_version: u32,
+    settings: u64,
+    region_path: PathBuf,
+    /// `None` when chunks skipped by chunk filters are missing
+    file: Option<FileStamp>,
+    /// Chunk results by chunk index
+    chunks: BTreeMap<usize, CachedChunk>,
+}
+
+/// The cached chunk results of one region file, loaded from and saved to `--cache-dir`.
+pub struct RegionCache {
+    cache_path: PathBuf,
+    region_path: PathBuf,
+    settings: u64,
+    file: Option<FileStamp>,
+    /// Results saved by the previous run, when its settings match
+    previous: BTreeMap<usize, CachedChunk>,
+    /// Whether the region file is the same size and age as when the cache was saved
+    file_unchanged: bool,
+    chunks: BTreeMap<usize, CachedChunk>,
+}
+
+impl RegionCache {
+    /// Loads the cached results of `region_path`, ignoring those saved with other settings
+    /// and unreadable cache files.
+    pub fn open(cache_dir: &Path, region_path: &Path, settings: u64) -> Self {
+        let region_path = std::fs::canonicalize(region_path).unwrap_or(region_path.to_path_buf());
+        let mut hasher = DefaultHasher::new();
+        region_path.hash(&mut hasher);
+        let cache_path = cache_dir.join(format!("{:016x}.json.gz", hasher.finish()));
+        let file = FileStamp::of(&region_path);
+
+        let saved = File::open(&cache_path).ok().and_then(|cache_file| {
+            serde_json::from_reader::<_, RegionCacheFile>(GzDecoder::new(BufReader::new(
+                cache_file,
+            )))
+            .ok()
+        });
+        let saved = saved.filter(|saved| {
+            saved.format_version == REGION_CACHE_FORMAT_VERSION
+                && saved.settings == settings
+                && saved.region_path == region_path
+        });
+        let file_unchanged = saved
+            .as_ref()
+            .is_some_and(|saved| file.is_some() && saved.file == file);
+        Self {
+            cache_path,
+            region_path,
+            settings,
+            file,
+            previous: saved.map(|saved| saved.chunks).unwrap_or_default(),
+            file_unchanged,
+            chunks: BTreeMap::new(),
+        }
+    }
+
+    /// Whether every chunk can be taken from the cache without reading the region file.
+    pub fn file_unchanged(&self) -> bool {
+        self.file_unchanged
+    }
+
+    /// The cached chunks by index, for an unchanged region file.
+    pub fn previous_chunks(&self) -> impl Iterator<Item = (usize, &CachedChunk)> {
+        self.previous.iter().map(|(&index, chunk)| (index, chunk))
+    }
+
+    /// Whether the results of chunk `index` were cached since it was last saved.
+    pub fn has(&self, index: usize, stamp: ChunkStamp) -> bool {
+        self.previous
+            .get(&index)
+            .is_some_and(|chunk| chunk.stamp == stamp)
+    }
+
+    /// Moves the cached results of chunk `index` to the new cache if it was not saved again
+    /// since, and returns them.
+    pub fn reuse(&mut self, index: usize, stamp: ChunkStamp) -> Option<&CachedChunk> {
+        let chunk = self.previous.remove(&index).filter(|c| c.stamp == stamp)?;
+        Some(self.chunks.entry(index).or_insert(chunk))
+    }
+
+    /// Records that some chunks of the region file are missing from the cache, so that the
+    /// next run reads the region file even if it did not change.
+    pub fn mark_incomplete(&mut self) {
+        self.file = None;
+    }
+
+    pub fn insert(&mut self, index: usize, chunk: CachedChunk) -> &CachedChunk {
+        self.chunks.insert(index, chunk);
+        &self.chunks[&index]
+    }
+
+    /// Writes the chunks reused or inserted since `open` to the cache directory.
+    pub fn save(self) -> Result<(), String> {
+        let error = |e: &dyn std::fmt::Display| {
+            format!(
+                "failed to write cache file '{}': {e}",
+                self.cache_path.display()
+            )
+        };
+        if let Some(dir) = self.cache_path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| error(&e))?;
+        }
+        let cache_file = RegionCacheFile {
+            format_version: REGION_CACHE_FORMAT_VERSION,
+            settings: self.settings,
+            region_path: self.region_path.clone(),
+            file: self.file,
+            chunks: self.chunks,
+        };
+        // Written aside and renamed, so that an interrupted run leaves no partial file
+        let temp_path = self.cache_path.with_extension("gz.tmp");
+        let file = BufWriter::new(File::create(&temp_path).map_err(|e| error(&e))?);
+        let mut encoder = GzEncoder::new(file, Compression::fast());
+        serde_json::to_writer(&mut encoder, &cache_file).map_err(|e| error(&e))?;
+        encoder
+            .finish()
+            .and_then(|mut file| file.flush())
+            .map_err(|e| error(&e))?;
+        std::fs::rename(&temp_path, &self.cache_path).map_err(|e| error(&e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_chunk_stamps_from_the_header() {
+        let mut header = vec![0u8; 2 * SECTOR_SIZE];
+        header[4..8].copy_from_slice(&0x0000_0201u32.to_be_bytes());
+        header[SECTOR_SIZE + 4..SECTOR_SIZE + 8].copy_from_slice(&1_700_000_000u32.to_be_bytes());
+        assert_eq!(
+            ChunkStamp::from_header(&header, 1),
+            Some(ChunkStamp {
+                location: 0x0201,
+                timestamp: 1_700_000_000
+            })
+        );
+        assert_eq!(ChunkStamp::from_header(&header[..100], 1), None);
+    }
+
+    #[test]
+    fn reuses_only_chunks_with_the_same_stamp() {
+        let dir = std::env::temp_dir().join(format!(
+            "nbt-sniffer-region-cache-test-{}",
+            std::process::id()
+        ));
+        let region_path = dir.join("r.0.0.mca");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&region_path, [0u8; 2 * SECTOR_SIZE]).unwrap();
+        let stamp = |timestamp| ChunkStamp {
+            location: 0x0201,
+            timestamp,
+        };
+        let chunk = |id: &str, timestamp| {
+            let mut output = TaskOutput::default();
+            output.counter.add(id.to_string(), None, 2);
+            CachedChunk::from_output(stamp(timestamp), output)
+        };
+
+        let mut cache = RegionCache::open(&dir, &region_path, 7);
+        assert!(!cache.file_unchanged());
+        cache.insert(0, chunk("minecraft:diamond", 100));
+        cache.insert(1, chunk("minecraft:emerald", 100));
+        cache.save().unwrap();
+
+        let mut cache = RegionCache::open(&dir, &region_path, 7);
+        assert!(cache.file_unchanged());
+        assert!(cache.reuse(0, stamp(100)).is_some());
+        assert!(cache.reuse(1, stamp(101)).is_none());
+        assert_eq!(
+            RegionCache::open(&dir, &region_path, 8)
+                .previous_chunks()
+                .count(),
+            0
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
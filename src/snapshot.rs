@@ -0,0 +1,185 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::{ScanResult, counter::CounterMap, occurrences::Occurrence};
+
+/// Bumped whenever a change to the snapshot layout would make older snapshots misread.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Scan results saved with `--save-snapshot`, as JSON, gzip-compressed when the file name
+/// ends in `.gz`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot<'a> {
+    pub format_version: u32,
+    pub tool_version: String,
+    pub world_path: PathBuf,
+    /// Seconds since the Unix epoch
+    pub scanned_at: u64,
+    /// Whether item counts are split by nesting depth
+    pub group_by_depth: bool,
+    pub counter_map: Cow<'a, CounterMap>,
+    /// Every matched item, saved with `--snapshot-locations`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub occurrences: Option<Cow<'a, [Occurrence]>>,
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    format_version: u32,
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn new(
+        world_path: PathBuf,
+        scanned_at: u64,
+        group_by_depth: bool,
+        counter_map: &'a CounterMap,
+        occurrences: Option<&'a [Occurrence]>,
+    ) -> Self {
+        Self {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            world_path,
+            scanned_at,
+            group_by_depth,
+            counter_map: Cow::Borrowed(counter_map),
+            occurrences: occurrences.map(Cow::Borrowed),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let error = |e: &dyn std::fmt::Display| {
+            format!("failed to write snapshot '{}': {e}", path.display())
+        };
+        let file = BufWriter::new(File::create(path).map_err(|e| error(&e))?);
+        if path.extension().is_some_and(|ext| ext == "gz") {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            serde_json::to_writer(&mut encoder, self).map_err(|e| error(&e))?;
+            encoder
+                .finish()
+                .and_then(|mut file| file.flush())
+                .map_err(|e| error(&e))
+        } else {
+            let mut file = file;
+            serde_json::to_writer(&mut file, self).map_err(|e| error(&e))?;
+            file.flush().map_err(|e| error(&e))
+        }
+    }
+}
+
+impl Snapshot<'static> {
+    /// Reads a snapshot, compressed or not, rejecting other format versions.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| {
+            format!("failed to read snapshot '{}': {e}", path.display())
+        };
+        let mut data = Vec::new();
+        BufReader::new(File::open(path).map_err(|e| error(&e))?)
+            .read_to_end(&mut data)
+            .map_err(|e| error(&e))?;
+        if data.starts_with(&GZIP_MAGIC) {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(data.as_slice())
+                .read_to_end(&mut decompressed)
+                .map_err(|e| error(&e))?;
+            data = decompressed;
+        }
+
+        // Parsed once, so that the version is checked without reading a large file twice
+        let value: serde_json::Value = serde_json::from_slice(&data).map_err(|e| error(&e))?;
+        drop(data);
+        let header = SnapshotHeader::deserialize(&value).map_err(|e| error(&e))?;
+        if header.format_version != SNAPSHOT_FORMAT_VERSION {
+            return Err(error(&format!(
+                "unsupported format version {} (expected {SNAPSHOT_FORMAT_VERSION})",
+                header.format_version
+            )));
+        }
+        serde_json::from_value(value).map_err(|e| error(&e))
+    }
+
+    /// Rebuilds the scan result, merging counts across depths unless `group_by_depth`.
+    pub fn into_scan_result(self, group_by_depth: bool) -> ScanResult {
+        let mut counter_map = self.counter_map.into_owned();
+        if !group_by_depth && self.group_by_depth {
+            let mut merged = CounterMap::new();
+            for (scope, counter) in counter_map.iter() {
+                let target = merged.entry_counter(scope.clone());
+                for (key, &count) in counter.detailed_counts() {
+                    target.add_key(key.clone().with_depth(None), count);
+                }
+            }
+            counter_map = merged;
+        }
+        ScanResult {
+            counter_map,
+            occurrences: self.occurrences.map(Cow::into_owned).unwrap_or_default(),
+            ..ScanResult::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataType, Scope, counter::ItemKey};
+
+    #[test]
+    fn round_trips_compressed_snapshots() {
+        let path = std::env::temp_dir().join(format!(
+            "nbt-sniffer-snapshot-test-{}.json.gz",
+            std::process::id()
+        ));
+        let scope = Scope {
+            dimension: "overworld".to_string(),
+            data_type: DataType::BlockEntity,
+        };
+        let components = valence_nbt::snbt::from_snbt_str(r#"{"minecraft:damage":3}"#).unwrap();
+        let mut counter_map = CounterMap::new();
+        let counter = counter_map.entry_counter(scope.clone());
+        counter.add_at_depth("minecraft:diamond".to_string(), None, Some(0), 3);
+        counter.add_at_depth("minecraft:diamond".to_string(), None, Some(1), 4);
+        counter.add("minecraft:bow".to_string(), Some(&components), 1);
+
+        Snapshot::new(PathBuf::from("world"), 42, true, &counter_map, None)
+            .save(&path)
+            .unwrap();
+        let snapshot = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot.world_path, PathBuf::from("world"));
+        assert_eq!(snapshot.scanned_at, 42);
+        let result = snapshot.into_scan_result(false);
+        let counts = result.counter_map.combined();
+        assert_eq!(counts.detailed_counts().len(), 2);
+        assert_eq!(
+            counts.detailed_counts()[&ItemKey::new("minecraft:diamond".to_string(), None)],
+            7
+        );
+        assert_eq!(
+            counts.detailed_counts()[&ItemKey::new("minecraft:bow".to_string(), Some(&components))],
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_other_format_versions() {
+        let path = std::env::temp_dir().join(format!(
+            "nbt-sniffer-snapshot-version-test-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, r#"{"format_version":999}"#).unwrap();
+        let error = Snapshot::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.contains("unsupported format version 999"));
+    }
+}
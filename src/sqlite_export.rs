@@ -0,0 +1,255 @@
+use std::{io, path::Path};
+
+use rusqlite::{Connection, params};
+
+use crate::{
+    nbt_utils::nbt_to_json,
+    occurrences::{Occurrence, OccurrenceSink},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE scan_metadata (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE sources (
+    source_id INTEGER PRIMARY KEY,
+    dimension TEXT NOT NULL,
+    data_type TEXT NOT NULL,
+    holder TEXT NOT NULL,
+    player TEXT,
+    x REAL,
+    y REAL,
+    z REAL,
+    chunk_x INTEGER,
+    chunk_z INTEGER
+);
+CREATE TABLE items (
+    occurrence_id INTEGER PRIMARY KEY,
+    source_id INTEGER NOT NULL REFERENCES sources (source_id),
+    parent_id INTEGER REFERENCES items (occurrence_id),
+    depth INTEGER NOT NULL,
+    slot INTEGER,
+    id TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    components_snbt TEXT,
+    components_json TEXT
+);
+";
+
+const INDEXES: &str = "
+CREATE INDEX items_id ON items (id);
+CREATE INDEX items_source ON items (source_id);
+CREATE INDEX sources_position ON sources (dimension, x, y, z);
+CREATE INDEX sources_chunk ON sources (dimension, chunk_x, chunk_z);
+";
+
+/// Writes occurrences to a new SQLite database with `sources`, `items` and
+/// `scan_metadata` tables, in a single transaction committed by `finish`.
+pub struct SqliteSink {
+    connection: Connection,
+    sources: u64,
+    items: u64,
+}
+
+fn to_io_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl SqliteSink {
+    /// Creates the database at `path`, replacing any existing file, and records
+    /// `metadata` as key-value pairs.
+    pub fn create(path: &Path, metadata: &[(&str, String)]) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| {
+            format!("failed to create SQLite export '{}': {e}", path.display())
+        };
+        if path.exists() {
+            std::fs::remove_file(path).map_err(|e| error(&e))?;
+        }
+        let connection = Connection::open(path).map_err(|e| error(&e))?;
+        connection
+            .execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF; BEGIN;")
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .map_err(|e| error(&e))?;
+        for (key, value) in metadata {
+            connection
+                .execute(
+                    "INSERT INTO scan_metadata (key, value) VALUES (?1, ?2)",
+                    params![key, value],
+                )
+                .map_err(|e| error(&e))?;
+        }
+        Ok(Self {
+            connection,
+            sources: 0,
+            items: 0,
+        })
+    }
+
+    fn insert_source(&mut self, occurrence: &Occurrence) -> rusqlite::Result<i64> {
+        let [x, y, z] = occurrence.coordinates.map_or([None; 3], |c| c.map(Some));
+        let chunk = |coordinate: Option<f64>| coordinate.map(|c| (c.floor() as i64).div_euclid(16));
+        self.connection
+            .prepare_cached(
+                "INSERT INTO sources (dimension, data_type, holder, player, x, y, z, chunk_x, chunk_z)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?
+            .execute(params![
+                occurrence.dimension,
+                format!("{:?}", occurrence.data_type),
+                occurrence.holder,
+                occurrence.player,
+                x,
+                y,
+                z,
+                chunk(x),
+                chunk(z),
+            ])?;
+        self.sources += 1;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    fn insert_batch(&mut self, batch: &[Occurrence]) -> rusqlite::Result<()> {
+        let mut occurrence_ids = Vec::with_capacity(batch.len());
+        let mut source: Option<(&Occurrence, i64)> = None;
+
+        for occurrence in batch {
+            let source_id = match source {
+                Some((first, source_id)) if first.same_holder(occurrence) => source_id,
+                _ => {
+                    let source_id = self.insert_source(occurrence)?;
+                    source = Some((occurrence, source_id));
+                    source_id
+                }
+            };
+            let parent_id = occurrence
+                .parent
+                .and_then(|parent| occurrence_ids.get(parent).copied());
+
+            self.connection
+                .prepare_cached(
+                    "INSERT INTO items (source_id, parent_id, depth, slot, id, count, components_snbt, components_json)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                )?
+                .execute(params![
+                    source_id,
+                    parent_id,
+                    occurrence.path.len() as i64,
+                    occurrence.slot,
+                    occurrence.id,
+                    occurrence.count as i64,
+                    occurrence
+                        .components
+                        .as_ref()
+                        .map(valence_nbt::snbt::to_snbt_string),
+                    occurrence
+                        .components
+                        .as_ref()
+                        .map(|c| nbt_to_json(c).to_string()),
+                ])?;
+            occurrence_ids.push(self.connection.last_insert_rowid());
+            self.items += 1;
+        }
+        Ok(())
+    }
+}
+
+impl OccurrenceSink for SqliteSink {
+    fn write_batch(&mut self, batch: &[Occurrence]) -> io::Result<()> {
+        self.insert_batch(batch).map_err(to_io_error)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let counts = [("sources", self.sources), ("items", self.items)];
+        for (key, count) in counts {
+            self.connection
+                .execute(
+                    "INSERT INTO scan_metadata (key, value) VALUES (?1, ?2)",
+                    params![key, count.to_string()],
+                )
+                .map_err(to_io_error)?;
+        }
+        self.connection
+            .execute_batch(INDEXES)
+            .and_then(|_| self.connection.execute_batch("COMMIT;"))
+            .map_err(to_io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataType, Scope, occurrences::Holder};
+
+    #[test]
+    fn writes_sources_and_nested_items() {
+        let path = std::env::temp_dir().join(format!(
+            "nbt-sniffer-export-test-{}.sqlite",
+            std::process::id()
+        ));
+        let scope = Scope {
+            dimension: "overworld".to_string(),
+            data_type: DataType::BlockEntity,
+        };
+        let chest = Holder {
+            scope: &scope,
+            id: "minecraft:chest",
+            position: "-1 64 17",
+            coordinates: Some([-1.0, 64.0, 17.0]),
+            player: None,
+        };
+        let shulker_path = ["minecraft:shulker_box".to_string()];
+        let batch = [
+            Occurrence::new(
+                &chest,
+                Some(0),
+                &[],
+                None,
+                "minecraft:shulker_box".to_string(),
+                1,
+                None,
+            ),
+            Occurrence::new(
+                &chest,
+                None,
+                &shulker_path,
+                Some(0),
+                "minecraft:diamond".to_string(),
+                64,
+                None,
+            ),
+        ];
+
+        let mut sink = SqliteSink::create(&path, &[("world_path", "world".to_string())]).unwrap();
+        sink.write_batch(&batch).unwrap();
+        sink.finish().unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let (chunk_x, chunk_z): (i64, i64) = connection
+            .query_row("SELECT chunk_x, chunk_z FROM sources", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((chunk_x, chunk_z), (-1, 1));
+        let (parent, depth): (String, i64) = connection
+            .query_row(
+                "SELECT parent.id, child.depth FROM items child
+                 JOIN items parent ON child.parent_id = parent.occurrence_id",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((parent.as_str(), depth), ("minecraft:shulker_box", 1));
+        let items: String = connection
+            .query_row(
+                "SELECT value FROM scan_metadata WHERE key = 'items'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(items, "2");
+
+        drop(connection);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,435 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::Read,
+    path::Path,
+};
+
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::filter::ItemQuery;
+
+/// Folder names used for item tags inside `data/<namespace>/tags/`.
+/// Minecraft 1.21 renamed `items` to `item`; both are accepted.
+const ITEM_TAG_FOLDERS: [&str; 2] = ["item", "items"];
+
+/// A single entry of a tag file's `values` array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagEntry {
+    /// Item ID, or tag ID when `is_tag` is set (without the leading `#`).
+    pub id: String,
+    pub is_tag: bool,
+    pub required: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTagEntry {
+    Plain(String),
+    Object {
+        id: String,
+        #[serde(default = "default_required")]
+        required: bool,
+    },
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct RawTagFile {
+    #[serde(default)]
+    replace: bool,
+    #[serde(default)]
+    values: Vec<RawTagEntry>,
+}
+
+impl From<RawTagEntry> for TagEntry {
+    fn from(raw: RawTagEntry) -> Self {
+        let (id, required) = match raw {
+            RawTagEntry::Plain(id) => (id, true),
+            RawTagEntry::Object { id, required } => (id, required),
+        };
+        match id.strip_prefix('#') {
+            Some(tag_id) => TagEntry {
+                id: normalize_id(tag_id),
+                is_tag: true,
+                required,
+            },
+            None => TagEntry {
+                id: normalize_id(&id),
+                is_tag: false,
+                required,
+            },
+        }
+    }
+}
+
+/// Adds the `minecraft:` namespace to IDs that don't specify one.
+pub fn normalize_id(id: &str) -> String {
+    if id.contains(':') {
+        id.to_string()
+    } else {
+        format!("minecraft:{id}")
+    }
+}
+
+/// Item tag definitions collected from vanilla data and datapacks.
+#[derive(Debug, Default)]
+pub struct TagRegistry {
+    tags: HashMap<String, Vec<TagEntry>>,
+}
+
+impl TagRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the item tags visible to a world: first the optional vanilla data
+    /// (a directory or client `.jar`), then every datapack in `<world>/datapacks`.
+    pub fn load(world_root: &Path, vanilla_data: Option<&Path>, verbose: bool) -> Self {
+        let mut registry = Self::new();
+
+        if let Some(vanilla_path) = vanilla_data {
+            registry.load_pack(vanilla_path, verbose);
+        }
+
+        let datapacks_dir = world_root.join("datapacks");
+        if let Ok(entries) = fs::read_dir(&datapacks_dir) {
+            let mut pack_paths: Vec<_> = entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .collect();
+            pack_paths.sort();
+            for pack_path in pack_paths {
+                registry.load_pack(&pack_path, verbose);
+            }
+        }
+
+        registry
+    }
+
+    /// Loads a single pack, which may be a directory or a zip archive (datapack or client jar).
+    fn load_pack(&mut self, pack_path: &Path, verbose: bool) {
+        let result = if pack_path.is_dir() {
+            self.load_pack_dir(pack_path)
+        } else if pack_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip") || ext.eq_ignore_ascii_case("jar"))
+        {
+            self.load_pack_archive(pack_path)
+        } else {
+            return;
+        };
+
+        match result {
+            Ok(loaded) => {
+                if verbose {
+                    eprintln!(
+                        "Loaded {loaded} item tag files from {}",
+                        pack_path.display()
+                    );
+                }
+            }
+            Err(e) => eprintln!(
+                "Warning: failed to load item tags from '{}': {e}",
+                pack_path.display()
+            ),
+        }
+    }
+
+    fn load_pack_dir(&mut self, pack_dir: &Path) -> Result<usize, String> {
+        // Accept both a pack root (containing `data/`) and a `data/` folder itself.
+        let data_dir = if pack_dir.join("data").is_dir() {
+            pack_dir.join("data")
+        } else {
+            pack_dir.to_path_buf()
+        };
+
+        let mut loaded = 0;
+        for entry in WalkDir::new(&data_dir).into_iter().filter_map(Result::ok) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(&data_dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let Some(tag_id) = tag_id_from_data_path(&relative) else {
+                continue;
+            };
+            let result = fs::read_to_string(entry.path())
+                .map_err(|e| format!("failed to read '{}': {e}", entry.path().display()))
+                .and_then(|contents| {
+                    self.add_tag_file(&tag_id, &contents)
+                        .map_err(|e| format!("invalid tag file '{}': {e}", entry.path().display()))
+                });
+            loaded += count_loaded(result);
+        }
+        Ok(loaded)
+    }
+
+    fn load_pack_archive(&mut self, archive_path: &Path) -> Result<usize, String> {
+        let file = fs::File::open(archive_path).map_err(|e| e.to_string())?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+
+        let mut loaded = 0;
+        for index in 0..archive.len() {
+            let mut zip_file = match archive.by_index(index) {
+                Ok(zip_file) => zip_file,
+                Err(e) => {
+                    loaded += count_loaded(Err(format!("failed to read entry {index}: {e}")));
+                    continue;
+                }
+            };
+            let name = zip_file.name().to_string();
+            let Some(tag_id) = name.strip_prefix("data/").and_then(tag_id_from_data_path) else {
+                continue;
+            };
+            let mut contents = String::new();
+            let result = zip_file
+                .read_to_string(&mut contents)
+                .map_err(|e| format!("failed to read '{name}': {e}"))
+                .and_then(|_| {
+                    self.add_tag_file(&tag_id, &contents)
+                        .map_err(|e| format!("invalid tag file '{name}': {e}"))
+                });
+            loaded += count_loaded(result);
+        }
+        Ok(loaded)
+    }
+
+    /// Parses a tag JSON file and merges it into the tag `tag_id`.
+    /// Files are appended to earlier definitions unless they set `replace: true`.
+    pub fn add_tag_file(&mut self, tag_id: &str, json: &str) -> Result<(), String> {
+        let raw: RawTagFile = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let entries = raw.values.into_iter().map(TagEntry::from);
+        let tag = self.tags.entry(normalize_id(tag_id)).or_default();
+        if raw.replace {
+            tag.clear();
+        }
+        tag.extend(entries);
+        Ok(())
+    }
+
+    pub fn contains(&self, tag_id: &str) -> bool {
+        self.tags.contains_key(tag_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Expands `tag_id` into the set of item IDs it contains, following nested tag references.
+    /// Optional (`required: false`) references to unknown tags are skipped; unknown required
+    /// references and reference cycles are reported as errors.
+    pub fn resolve(&self, tag_id: &str) -> Result<BTreeSet<String>, String> {
+        let mut items = BTreeSet::new();
+        let mut stack = Vec::new();
+        self.resolve_into(tag_id, &mut items, &mut stack)?;
+        Ok(items)
+    }
+
+    fn resolve_into(
+        &self,
+        tag_id: &str,
+        items: &mut BTreeSet<String>,
+        stack: &mut Vec<String>,
+    ) -> Result<(), String> {
+        if stack.iter().any(|t| t == tag_id) {
+            return Err(format!(
+                "cyclic tag reference: #{} -> #{tag_id}",
+                stack.join(" -> #")
+            ));
+        }
+        let Some(entries) = self.tags.get(tag_id) else {
+            return Err(format!("unknown item tag #{tag_id}"));
+        };
+
+        stack.push(tag_id.to_string());
+        for entry in entries {
+            if !entry.is_tag {
+                items.insert(entry.id.clone());
+            } else if self.contains(&entry.id) {
+                self.resolve_into(&entry.id, items, stack)?;
+            } else if entry.required {
+                return Err(format!(
+                    "item tag #{tag_id} references unknown tag #{}",
+                    entry.id
+                ));
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+/// Warns about a tag file that could not be loaded, so that the rest of the pack still is.
+/// Returns the number of files loaded.
+fn count_loaded(result: Result<(), String>) -> usize {
+    match result {
+        Ok(()) => 1,
+        Err(e) => {
+            eprintln!("Warning: skipped {e}");
+            0
+        }
+    }
+}
+
+/// Maps a path relative to `data/` (e.g. `minecraft/tags/item/logs.json`) to its tag ID.
+fn tag_id_from_data_path(relative: &str) -> Option<String> {
+    let (namespace, rest) = relative.split_once('/')?;
+    let rest = rest.strip_prefix("tags/")?;
+    let (folder, tag_path) = rest.split_once('/')?;
+    if !ITEM_TAG_FOLDERS.contains(&folder) {
+        return None;
+    }
+    let tag_path = tag_path.strip_suffix(".json")?;
+    Some(format!("{namespace}:{tag_path}"))
+}
+
+/// Expands the tag of every `ItemFilter` in the query that uses one, reporting the result in verbose mode.
+/// Tags that cannot be resolved match no items.
+pub fn resolve_item_filter_tags(query: &mut ItemQuery, registry: &TagRegistry, verbose: bool) {
+    query.for_each_item_filter_mut(|filter| {
+        let Some(tag_id) = &filter.tag else {
+            return;
+        };
+        let members = match registry.resolve(tag_id) {
+            Ok(members) => members,
+            Err(e) => {
+                eprintln!("Warning: failed to resolve #{tag_id}: {e}");
+                BTreeSet::new()
+            }
+        };
+        if verbose {
+            eprintln!(
+                "Tag #{tag_id} expands to {} items: {}",
+                members.len(),
+                members.iter().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+        filter.tag_members = Some(members.into_iter().collect());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(files: &[(&str, &str)]) -> TagRegistry {
+        let mut registry = TagRegistry::new();
+        for (tag_id, json) in files {
+            registry.add_tag_file(tag_id, json).unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn tag_id_from_data_path_accepts_both_folder_names() {
+        assert_eq!(
+            tag_id_from_data_path("minecraft/tags/item/logs.json"),
+            Some("minecraft:logs".to_string())
+        );
+        assert_eq!(
+            tag_id_from_data_path("custom/tags/items/nested/gems.json"),
+            Some("custom:nested/gems".to_string())
+        );
+        assert_eq!(
+            tag_id_from_data_path("minecraft/tags/block/logs.json"),
+            None
+        );
+        assert_eq!(tag_id_from_data_path("minecraft/recipe/stick.json"), None);
+    }
+
+    #[test]
+    fn resolve_nested_tags() {
+        let registry = registry(&[
+            (
+                "minecraft:oak_logs",
+                r#"{"values":["oak_log","minecraft:oak_wood"]}"#,
+            ),
+            (
+                "minecraft:logs",
+                r##"{"values":["#minecraft:oak_logs","minecraft:crimson_stem"]}"##,
+            ),
+        ]);
+        let members = registry.resolve("minecraft:logs").unwrap();
+        assert_eq!(
+            members.into_iter().collect::<Vec<_>>(),
+            vec![
+                "minecraft:crimson_stem",
+                "minecraft:oak_log",
+                "minecraft:oak_wood"
+            ]
+        );
+    }
+
+    #[test]
+    fn optional_missing_tag_is_skipped() {
+        let registry = registry(&[(
+            "minecraft:gems",
+            r##"{"values":["minecraft:diamond",{"id":"#othermod:gems","required":false}]}"##,
+        )]);
+        let members = registry.resolve("minecraft:gems").unwrap();
+        assert_eq!(members.len(), 1);
+        assert!(members.contains("minecraft:diamond"));
+    }
+
+    #[test]
+    fn required_missing_tag_is_an_error() {
+        let registry = registry(&[("minecraft:gems", r##"{"values":["#othermod:gems"]}"##)]);
+        assert!(registry.resolve("minecraft:gems").is_err());
+        assert!(registry.resolve("minecraft:unknown").is_err());
+    }
+
+    #[test]
+    fn cyclic_tags_are_an_error() {
+        let registry = registry(&[
+            ("minecraft:a", r##"{"values":["#minecraft:b"]}"##),
+            ("minecraft:b", r##"{"values":["#minecraft:a"]}"##),
+        ]);
+        assert!(registry.resolve("minecraft:a").is_err());
+    }
+
+    #[test]
+    fn later_files_append_unless_replace() {
+        let mut registry = registry(&[("minecraft:gems", r#"{"values":["diamond"]}"#)]);
+        registry
+            .add_tag_file("minecraft:gems", r#"{"values":["emerald"]}"#)
+            .unwrap();
+        assert_eq!(registry.resolve("minecraft:gems").unwrap().len(), 2);
+
+        registry
+            .add_tag_file(
+                "minecraft:gems",
+                r#"{"replace":true,"values":["amethyst_shard"]}"#,
+            )
+            .unwrap();
+        let members = registry.resolve("minecraft:gems").unwrap();
+        assert_eq!(members.len(), 1);
+        assert!(members.contains("minecraft:amethyst_shard"));
+    }
+
+    #[test]
+    fn invalid_tag_file_does_not_stop_the_pack() {
+        let dir =
+            std::env::temp_dir().join(format!("nbt-sniffer-tags-test-{}", std::process::id()));
+        let tags_dir = dir.join("data/minecraft/tags/item");
+        fs::create_dir_all(&tags_dir).unwrap();
+        fs::write(tags_dir.join("a_broken.json"), "{values:").unwrap();
+        fs::write(
+            tags_dir.join("b_gems.json"),
+            r#"{"values":["minecraft:diamond"]}"#,
+        )
+        .unwrap();
+
+        let mut registry = TagRegistry::new();
+        assert_eq!(registry.load_pack_dir(&dir), Ok(1));
+        assert!(registry.contains("minecraft:b_gems"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use valence_nbt::value::ValueRef;
+
+/// Flattens a text component to the plain text a player would see, dropping colors and
+/// other styling. Accepts the NBT forms used since 1.21.5 (strings, compounds with `text`,
+/// `translate` and `extra`, lists) as well as older JSON-string encodings.
+pub fn flatten_text_component(component: ValueRef) -> String {
+    let mut out = String::new();
+    match component {
+        // Only a whole component can be a legacy JSON string; strings nested in NBT are text
+        ValueRef::String(s) => match parse_json_component(s) {
+            Some(json) => flatten_json_into(&json, &mut out),
+            None => out.push_str(s),
+        },
+        _ => flatten_nbt_into(component, &mut out),
+    }
+    out
+}
+
+fn flatten_nbt_into(component: ValueRef, out: &mut String) {
+    match component {
+        ValueRef::String(s) => out.push_str(s),
+        ValueRef::Compound(compound) => {
+            // Heterogeneous lists wrap non-compound elements as `{"": value}`
+            let content = ["text", "", "translate", "keybind"]
+                .iter()
+                .find_map(|key| compound.get(*key));
+            match (content, compound.get("fallback")) {
+                (Some(_), Some(fallback)) if compound.contains_key("translate") => {
+                    flatten_nbt_into(fallback.as_value_ref(), out);
+                }
+                (Some(content), _) => flatten_nbt_into(content.as_value_ref(), out),
+                (None, _) => {}
+            }
+            if let Some(extra) = compound.get("extra") {
+                flatten_nbt_into(extra.as_value_ref(), out);
+            }
+        }
+        ValueRef::List(list) => {
+            for element in list.iter() {
+                flatten_nbt_into(element, out);
+            }
+        }
+        ValueRef::Byte(v) => out.push_str(&v.to_string()),
+        ValueRef::Short(v) => out.push_str(&v.to_string()),
+        ValueRef::Int(v) => out.push_str(&v.to_string()),
+        ValueRef::Long(v) => out.push_str(&v.to_string()),
+        ValueRef::Float(v) => out.push_str(&v.to_string()),
+        ValueRef::Double(v) => out.push_str(&v.to_string()),
+        ValueRef::ByteArray(_) | ValueRef::IntArray(_) | ValueRef::LongArray(_) => {}
+    }
+}
+
+/// Before 1.21.5, text components were stored as JSON strings, e.g. `'{"text":"Excalibur"}'`.
+/// Plain strings that merely look like JSON are kept as-is.
+fn parse_json_component(s: &str) -> Option<serde_json::Value> {
+    let trimmed = s.trim_start();
+    if !(trimmed.starts_with('{') || trimmed.starts_with('[') || trimmed.starts_with('"')) {
+        return None;
+    }
+    serde_json::from_str(s).ok()
+}
+
+fn flatten_json_into(component: &serde_json::Value, out: &mut String) {
+    use serde_json::Value as Json;
+
+    match component {
+        Json::String(s) => out.push_str(s),
+        Json::Number(n) => out.push_str(&n.to_string()),
+        Json::Bool(b) => out.push_str(&b.to_string()),
+        Json::Array(elements) => {
+            for element in elements {
+                flatten_json_into(element, out);
+            }
+        }
+        Json::Object(object) => {
+            if object.contains_key("translate")
+                && let Some(fallback) = object.get("fallback")
+            {
+                flatten_json_into(fallback, out);
+            } else if let Some(content) = ["text", "translate", "keybind"]
+                .iter()
+                .find_map(|key| object.get(*key))
+            {
+                flatten_json_into(content, out);
+            }
+            if let Some(extra) = object.get("extra") {
+                flatten_json_into(extra, out);
+            }
+        }
+        Json::Null => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence_nbt::{Value, snbt::from_snbt_str};
+
+    fn flatten_snbt(snbt: &str) -> String {
+        let value: Value = from_snbt_str(snbt).unwrap();
+        flatten_text_component(value.as_value_ref())
+    }
+
+    #[test]
+    fn flattens_nbt_components() {
+        assert_eq!(flatten_snbt(r#""Excalibur""#), "Excalibur");
+        assert_eq!(
+            flatten_snbt(
+                r#"{text:"Excalibur",color:"gold",italic:0b,extra:[{text:" of ",bold:1b},{text:"Doom"}]}"#
+            ),
+            "Excalibur of Doom"
+        );
+        assert_eq!(
+            flatten_snbt(r#"[{text:"A"},{text:"B",extra:["C"]}]"#),
+            "ABC"
+        );
+
+        let mut wrapped = valence_nbt::Compound::new();
+        wrapped.insert("", "Wrapped");
+        assert_eq!(
+            flatten_text_component(Value::Compound(wrapped).as_value_ref()),
+            "Wrapped"
+        );
+        assert_eq!(
+            flatten_snbt(r#"{translate:"item.minecraft.diamond",fallback:"Diamond"}"#),
+            "Diamond"
+        );
+        assert_eq!(
+            flatten_snbt(r#"{translate:"item.minecraft.diamond"}"#),
+            "item.minecraft.diamond"
+        );
+    }
+
+    #[test]
+    fn flattens_legacy_json_strings() {
+        let flatten_string =
+            |s: &str| flatten_text_component(Value::String(s.to_string()).as_value_ref());
+        assert_eq!(
+            flatten_string(r#"{"text":"","extra":[{"text":"Old ","color":"red"},"Blade"]}"#),
+            "Old Blade"
+        );
+        assert_eq!(flatten_string(r#""Quoted""#), "Quoted");
+        assert_eq!(flatten_string(r#"[{"text":"A"},"B"]"#), "AB");
+        assert_eq!(flatten_string("[Rare"), "[Rare");
+    }
+
+    #[test]
+    fn keeps_nested_strings_literal() {
+        assert_eq!(flatten_snbt(r#"{text:"[1]"}"#), "[1]");
+        assert_eq!(flatten_snbt(r#"{text:"\"Quoted\""}"#), "\"Quoted\"");
+        assert_eq!(
+            flatten_snbt(r#"{translate:"x",fallback:"{\"text\":\"y\"}"}"#),
+            r#"{"text":"y"}"#
+        );
+        assert_eq!(
+            flatten_snbt(r#"[{text:"[1]"},{text:"",extra:["[2]"]}]"#),
+            "[1][2]"
+        );
+    }
+}
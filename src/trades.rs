@@ -0,0 +1,201 @@
+use serde::Serialize;
+use simdnbt::borrow::NbtCompound;
+
+use crate::{
+    enchantments::component_enchantments,
+    filter::{ItemContext, ItemQuery},
+    nbt_utils::{self, convert_simdnbt_to_valence_nbt, item_count},
+};
+
+/// One side of a trade offer.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TradeItem {
+    pub id: String,
+    pub count: u64,
+    /// Enchantments of the item, e.g. `minecraft:mending 1`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub enchantments: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<String>,
+}
+
+impl TradeItem {
+    fn from_nbt(item: NbtCompound) -> Option<Self> {
+        let id = item.string(nbt_utils::NBT_KEY_ID)?.to_string();
+        if id == "minecraft:air" {
+            return None;
+        }
+        let components = item
+            .compound(nbt_utils::NBT_KEY_COMPONENTS)
+            .map(|c| convert_simdnbt_to_valence_nbt(&c));
+        let mut enchantments: Vec<_> = components
+            .as_ref()
+            .map(component_enchantments)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| format!("{} {}", e.id, e.level))
+            .collect();
+        enchantments.sort();
+        Some(Self {
+            count: item_count(&item),
+            id,
+            enchantments,
+            components: components.map(|c| valence_nbt::snbt::to_snbt_string(&c)),
+        })
+    }
+
+    /// Short label such as `20 × minecraft:emerald` or `1 × minecraft:enchanted_book [minecraft:mending 1]`.
+    pub fn label(&self) -> String {
+        let mut label = format!("{} × {}", self.count, self.id);
+        if !self.enchantments.is_empty() {
+            label.push_str(&format!(" [{}]", self.enchantments.join(", ")));
+        }
+        label
+    }
+}
+
+/// A single `Offers.Recipes` entry of a villager or wandering trader.
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeOffer {
+    /// Entity ID of the trader
+    pub trader: String,
+    /// Villager profession, or the trader ID for wandering traders
+    pub profession: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+    pub dimension: String,
+    pub position: String,
+    pub buy: TradeItem,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_b: Option<TradeItem>,
+    pub sell: TradeItem,
+    pub uses: i32,
+    pub max_uses: i32,
+}
+
+impl TradeOffer {
+    /// Key used to group offers by profession, level and sold item.
+    pub fn group_key(&self) -> (&str, Option<i32>, &TradeItem) {
+        (&self.profession, self.level, &self.sell)
+    }
+
+    pub fn price_label(&self) -> String {
+        match &self.buy_b {
+            Some(buy_b) => format!("{} + {}", self.buy.label(), buy_b.label()),
+            None => self.buy.label(),
+        }
+    }
+}
+
+/// Extracts the trade offers of `entity_nbt` for which the buy, second buy or sell item
+/// matches `queries`. Entities without offers, or not accepted by the holder filters,
+/// yield nothing.
+pub fn extract_trade_offers(
+    entity_nbt: NbtCompound,
+    entity_id: &str,
+    dimension: &str,
+    position: &str,
+    queries: &ItemQuery,
+) -> Vec<TradeOffer> {
+    let Some(recipes) = entity_nbt
+        .compound("Offers")
+        .and_then(|offers| offers.list("Recipes"))
+        .and_then(|recipes| recipes.compounds())
+    else {
+        return Vec::new();
+    };
+    if !queries.matches_holder(entity_id, entity_nbt) {
+        return Vec::new();
+    }
+
+    let villager_data = entity_nbt.compound("VillagerData");
+    let profession = villager_data
+        .and_then(|data| data.string("profession"))
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| entity_id.to_string());
+    let level = villager_data.and_then(|data| data.int("level"));
+
+    let mut offers = Vec::new();
+    for recipe in recipes {
+        let item_matches = |key: &str| {
+            recipe.compound(key).is_some_and(|item| {
+                item.string(nbt_utils::NBT_KEY_ID)
+                    .is_some_and(|id| queries.matches(&ItemContext::new(&id.to_str(), item)))
+            })
+        };
+        if !(item_matches("sell") || item_matches("buy") || item_matches("buyB")) {
+            continue;
+        }
+
+        let (Some(buy), Some(sell)) = (
+            recipe.compound("buy").and_then(TradeItem::from_nbt),
+            recipe.compound("sell").and_then(TradeItem::from_nbt),
+        ) else {
+            continue;
+        };
+        offers.push(TradeOffer {
+            trader: entity_id.to_string(),
+            profession: profession.clone(),
+            level,
+            dimension: dimension.to_string(),
+            position: position.to_string(),
+            buy,
+            buy_b: recipe.compound("buyB").and_then(TradeItem::from_nbt),
+            sell,
+            uses: recipe.int("uses").unwrap_or(0),
+            max_uses: recipe.int("maxUses").unwrap_or(0),
+        });
+    }
+    offers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_utils::snbt_to_binary;
+    use std::io::Cursor;
+
+    const LIBRARIAN: &str = r#"{id:"minecraft:villager",VillagerData:{profession:"minecraft:librarian",level:3,type:"minecraft:plains"},Offers:{Recipes:[
+        {buy:{id:"minecraft:emerald",count:20},buyB:{id:"minecraft:book",count:1},sell:{id:"minecraft:enchanted_book",count:1,components:{"minecraft:stored_enchantments":{"minecraft:mending":1}}},uses:2,maxUses:12},
+        {buy:{id:"minecraft:paper",count:24},sell:{id:"minecraft:emerald",count:1},uses:0,maxUses:16}
+    ]}}"#;
+
+    fn offers(query: &ItemQuery) -> Vec<TradeOffer> {
+        let bytes = snbt_to_binary(LIBRARIAN);
+        let nbt = simdnbt::borrow::read(&mut Cursor::new(&bytes[..]))
+            .unwrap()
+            .unwrap();
+        extract_trade_offers(
+            nbt.as_compound(),
+            "minecraft:villager",
+            "overworld",
+            "0 64 0",
+            query,
+        )
+    }
+
+    #[test]
+    fn extracts_all_offers() {
+        let offers = offers(&ItemQuery::match_all());
+        assert_eq!(offers.len(), 2);
+
+        let mending = &offers[0];
+        assert_eq!(mending.profession, "minecraft:librarian");
+        assert_eq!(mending.level, Some(3));
+        assert_eq!((mending.uses, mending.max_uses), (2, 12));
+        assert_eq!(mending.sell.enchantments, ["minecraft:mending 1"]);
+        assert_eq!(
+            mending.price_label(),
+            "20 × minecraft:emerald + 1 × minecraft:book"
+        );
+        assert!(offers[1].buy_b.is_none());
+    }
+
+    #[test]
+    fn keeps_offers_with_a_matching_item() {
+        let query = ItemQuery::from_expr(crate::filter::parse_filter_expr("paper").unwrap());
+        let offers = offers(&query);
+        assert_eq!(offers.len(), 1);
+        assert_eq!(offers[0].buy.id, "minecraft:paper");
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     DataType,
     counter::{Counter, CounterMap},
+    enchantments::Enchantment,
 };
 use std::collections::{BTreeMap, HashMap};
 
@@ -73,6 +74,38 @@ impl Aggregable for HashMap<String, u64> {
     }
 }
 
+impl Aggregable for HashMap<(String, Option<u32>), u64> {
+    fn new_empty() -> Self {
+        HashMap::new()
+    }
+
+    fn from_counter(counter: &Counter) -> Self {
+        counter.total_by_id_and_depth()
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (key, value) in other {
+            *self.entry(key.clone()).or_insert(0) += *value;
+        }
+    }
+}
+
+impl Aggregable for HashMap<Enchantment, u64> {
+    fn new_empty() -> Self {
+        HashMap::new()
+    }
+
+    fn from_counter(counter: &Counter) -> Self {
+        counter.total_by_enchantment()
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (key, value) in other {
+            *self.entry(key.clone()).or_insert(0) += *value;
+        }
+    }
+}
+
 /// Generic struct to hold aggregated data results.
 ///
 /// `T` is a type that implements the `Aggregable` trait, representing the
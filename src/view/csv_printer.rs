@@ -0,0 +1,177 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use super::{records::TabularRecord, structures::Report};
+use crate::DataType;
+
+/// Column value used for data types, matching the keys of the JSON output.
+fn data_type_name(data_type: DataType) -> String {
+    format!("{data_type:?}")
+}
+
+/// Writes every section of `report` as rows prefixed with `section`, `dimension` and
+/// `data_type` columns, which are left empty when they do not apply to the section.
+pub fn write_report_csv<TItem: TabularRecord + Serialize, W: Write>(
+    report: &Report<TItem>,
+    delimiter: u8,
+    out: W,
+) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+
+    let mut header = vec!["section", "dimension", "data_type"];
+    header.extend_from_slice(TItem::COLUMNS);
+    writer.write_record(&header)?;
+
+    let mut write_section =
+        |section: &str, dimension: &str, data_type: &str, items: &[TItem]| -> csv::Result<()> {
+            for item in items {
+                let mut row = vec![
+                    section.to_string(),
+                    dimension.to_string(),
+                    data_type.to_string(),
+                ];
+                row.extend(item.fields());
+                writer.write_record(&row)?;
+            }
+            Ok(())
+        };
+
+    if let Some(per_dimension_detail) = &report.per_dimension_detail {
+        let mut dimensions: Vec<_> = per_dimension_detail.keys().collect();
+        dimensions.sort();
+        for dimension in dimensions {
+            let type_map = &per_dimension_detail[dimension];
+            for data_type in DataType::iter() {
+                if let Some(items) = type_map.get(&data_type) {
+                    write_section(
+                        "per_dimension_detail",
+                        dimension,
+                        &data_type_name(data_type),
+                        items,
+                    )?;
+                }
+            }
+        }
+    }
+    if let Some(per_dimension) = &report.per_dimension_summary {
+        let mut dimensions: Vec<_> = per_dimension.keys().collect();
+        dimensions.sort();
+        for dimension in dimensions {
+            write_section("per_dimension", dimension, "", &per_dimension[dimension])?;
+        }
+    }
+    if let Some(per_data_type) = &report.per_data_type_summary {
+        for data_type in DataType::iter() {
+            if let Some(items) = per_data_type.get(&data_type) {
+                write_section("per_data_type", "", &data_type_name(data_type), items)?;
+            }
+        }
+    }
+    write_section("grand_total", "", "", &report.grand_total)?;
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes one row per record, after a header row.
+pub fn write_records_csv<TRecord: TabularRecord, W: Write>(
+    records: &[TRecord],
+    delimiter: u8,
+    out: W,
+) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+    writer.write_record(TRecord::COLUMNS)?;
+    for record in records {
+        writer.write_record(record.fields())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn print_report_as_csv<TItem: TabularRecord + Serialize>(
+    report: &Report<TItem>,
+    delimiter: u8,
+) {
+    if let Err(e) = write_report_csv(report, delimiter, io::stdout().lock()) {
+        eprintln!("Error writing delimited output: {e}");
+    }
+}
+
+pub fn print_records_as_csv<TRecord: TabularRecord>(records: &[TRecord], delimiter: u8) {
+    if let Err(e) = write_records_csv(records, delimiter, io::stdout().lock()) {
+        eprintln!("Error writing delimited output: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::structures::{ReportItemDetailed, ReportItemNbt};
+    use std::collections::HashMap;
+
+    fn item(id: &str, nbt: Option<&str>, count: u64) -> ReportItemDetailed {
+        ReportItemDetailed {
+            count,
+            id: id.to_string(),
+            depth: None,
+            nbt: nbt.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn writes_sections_with_quoted_snbt() {
+        let sword = item(
+            "minecraft:iron_sword",
+            Some(r#"{"minecraft:custom_name":"Big, \"bad\" sword"}"#),
+            2,
+        );
+        let report = Report {
+            per_dimension_summary: Some(HashMap::from([(
+                "overworld".to_string(),
+                vec![sword.clone()],
+            )])),
+            per_data_type_summary: Some(HashMap::from([(DataType::Entity, vec![sword.clone()])])),
+            per_dimension_detail: None,
+            grand_total: vec![sword, item("minecraft:dirt", None, 64)],
+            grand_total_count: 66,
+        };
+
+        let mut out = Vec::new();
+        write_report_csv(&report, b',', &mut out).unwrap();
+        let nbt = r#""{""minecraft:custom_name"":""Big, \""bad\"" sword""}""#;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "section,dimension,data_type,id,depth,nbt,count\n\
+                 per_dimension,overworld,,minecraft:iron_sword,,{nbt},2\n\
+                 per_data_type,,Entity,minecraft:iron_sword,,{nbt},2\n\
+                 grand_total,,,minecraft:iron_sword,,{nbt},2\n\
+                 grand_total,,,minecraft:dirt,,,64\n"
+            )
+        );
+    }
+
+    #[test]
+    fn tsv_uses_tabs() {
+        let mut out = Vec::new();
+        write_records_csv(
+            &[ReportItemNbt {
+                count: 3,
+                nbt: Some("{a:1,b:2}".to_string()),
+            }],
+            b'\t',
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "nbt\tcount\n{a:1,b:2}\t3\n"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use super::{
+    records::{TabularRecord, column_title},
+    structures::Report,
+};
+use crate::{DataType, cli::CliArgs, trades::TradeOffer, tree::ItemSummaryNode};
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2rem; color: #1f2328; background: #fff; }
+h1 { margin-bottom: 0.2rem; }
+.meta { color: #59636e; margin-top: 0; }
+section { margin: 1.5rem 0; }
+table { border-collapse: collapse; margin: 0.5rem 0 1rem; min-width: 30rem; }
+th, td { border: 1px solid #d1d9e0; padding: 0.3rem 0.6rem; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; cursor: pointer; user-select: none; white-space: nowrap; }
+th[data-order="asc"]::after { content: " \25B2"; }
+th[data-order="desc"]::after { content: " \25BC"; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+pre { margin: 0; font-size: 0.85em; }
+input.filter { padding: 0.3rem; width: 20rem; }
+details { margin-left: 1.2rem; }
+details > summary { cursor: pointer; }
+ul.tree { list-style: none; margin: 0; padding-left: 1.2rem; }
+"#;
+
+const SCRIPT: &str = r#"
+document.querySelectorAll("table.report").forEach(table => {
+  const body = table.tBodies[0];
+  table.querySelectorAll("th").forEach((th, column) => {
+    th.addEventListener("click", () => {
+      const order = th.dataset.order === "asc" ? "desc" : "asc";
+      table.querySelectorAll("th").forEach(other => delete other.dataset.order);
+      th.dataset.order = order;
+      const value = row => row.cells[column].textContent.trim();
+      const rows = Array.from(body.rows).sort((a, b) => {
+        const [x, y] = [value(a), value(b)];
+        const numeric = x !== "" && y !== "" && !isNaN(x) && !isNaN(y);
+        const cmp = numeric ? x - y : x.localeCompare(y);
+        return order === "asc" ? cmp : -cmp;
+      });
+      rows.forEach(row => body.appendChild(row));
+    });
+  });
+});
+document.querySelectorAll("input.filter").forEach(input => {
+  const table = document.getElementById(input.dataset.table);
+  input.addEventListener("input", () => {
+    const needle = input.value.toLowerCase();
+    Array.from(table.tBodies[0].rows).forEach(row => {
+      row.hidden = !row.textContent.toLowerCase().includes(needle);
+    });
+  });
+});
+"#;
+
+/// Escapes text for use in HTML content and attribute values.
+pub fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Indents SNBT with one entry per line. Typed arrays (`[I;1,2]`) and empty compounds and
+/// lists stay on one line.
+pub fn pretty_snbt(snbt: &str) -> String {
+    let mut out = String::with_capacity(snbt.len() * 2);
+    let mut indent = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = snbt.chars().peekable();
+    let new_line = |out: &mut String, indent: usize| {
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent));
+    };
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                quote = Some(c);
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                let close = if c == '{' { '}' } else { ']' };
+                let mut ahead = chars.clone();
+                let typed_array = c == '['
+                    && matches!(
+                        (ahead.next(), ahead.next()),
+                        (Some('B' | 'I' | 'L'), Some(';'))
+                    );
+                if chars.peek() == Some(&close) {
+                    out.push(close);
+                    chars.next();
+                } else if typed_array {
+                    for c in chars.by_ref() {
+                        out.push(c);
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                } else {
+                    indent += 1;
+                    new_line(&mut out, indent);
+                }
+            }
+            '}' | ']' => {
+                indent = indent.saturating_sub(1);
+                new_line(&mut out, indent);
+                out.push(c);
+            }
+            ',' => {
+                out.push(',');
+                new_line(&mut out, indent);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn world_subtitle(args: &CliArgs) -> String {
+    match &args.world_path {
+        Some(world_path) => format!("World: {}", world_path.display()),
+        None => String::new(),
+    }
+}
+
+/// Builds an HTML page, with its style and script inlined.
+fn html_page(title: &str, subtitle: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"meta\">{subtitle}</p>\n{body}<script>{SCRIPT}</script>\n</body>\n</html>\n",
+        title = html_escape(title),
+        subtitle = html_escape(subtitle),
+    )
+}
+
+/// Appends a filterable, sortable table of `records`.
+fn write_table<TRecord: TabularRecord>(out: &mut String, table_id: &str, records: &[TRecord]) {
+    let _ = writeln!(
+        out,
+        "<input class=\"filter\" type=\"search\" placeholder=\"Filter rows\" data-table=\"{table_id}\">"
+    );
+    let _ = writeln!(out, "<table class=\"report\" id=\"{table_id}\">");
+    out.push_str("<thead><tr>");
+    for column in TRecord::COLUMNS {
+        let _ = write!(out, "<th>{}</th>", column_title(column));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    for record in records {
+        out.push_str("<tr>");
+        for (column, field) in TRecord::COLUMNS.iter().zip(record.fields()) {
+            if *column == "nbt" && !field.is_empty() {
+                let _ = write!(
+                    out,
+                    "<td><pre>{}</pre></td>",
+                    html_escape(&pretty_snbt(&field))
+                );
+            } else if !field.is_empty() && field.parse::<f64>().is_ok() {
+                let _ = write!(out, "<td class=\"number\">{}</td>", html_escape(&field));
+            } else {
+                let _ = write!(out, "<td>{}</td>", html_escape(&field));
+            }
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+}
+
+/// Appends a section heading and its table, skipping empty sections.
+fn write_section<TRecord: TabularRecord>(
+    out: &mut String,
+    tables: &mut usize,
+    heading: &str,
+    title: &str,
+    records: &[TRecord],
+) {
+    if records.is_empty() {
+        return;
+    }
+    *tables += 1;
+    let _ = writeln!(out, "<{heading}>{}</{heading}>", html_escape(title));
+    write_table(out, &format!("table-{tables}"), records);
+}
+
+fn write_tree_node(out: &mut String, node: &ItemSummaryNode) {
+    let children = match node {
+        ItemSummaryNode::Root { children, .. } | ItemSummaryNode::Item { children, .. } => children,
+    };
+    let label = match node {
+        ItemSummaryNode::Item {
+            id,
+            count,
+            snbt: Some(snbt),
+            ..
+        } => format!(
+            "{count}x {}<pre>{}</pre>",
+            html_escape(id),
+            html_escape(&pretty_snbt(snbt))
+        ),
+        _ => html_escape(&node.to_string()),
+    };
+    if children.is_empty() {
+        let _ = writeln!(out, "<li>{label}</li>");
+        return;
+    }
+    let _ = writeln!(
+        out,
+        "<li><details><summary>{label}</summary>\n<ul class=\"tree\">"
+    );
+    for child in children {
+        write_tree_node(out, child);
+    }
+    out.push_str("</ul></details></li>\n");
+}
+
+/// Renders `report` and the per-source trees as a self-contained HTML page, with sections
+/// in the same order as the table output.
+pub fn render_report_html<TItem: TabularRecord + Serialize>(
+    report: &Report<TItem>,
+    source_trees: &[ItemSummaryNode],
+    args: &CliArgs,
+) -> String {
+    let mut body = String::new();
+    let mut tables = 0;
+
+    if let Some(per_dimension_detail) = &report.per_dimension_detail {
+        let mut dimensions: Vec<_> = per_dimension_detail.keys().collect();
+        dimensions.sort();
+        for dimension in dimensions {
+            body.push_str("<section>\n");
+            let _ = writeln!(body, "<h2>Dimension: {}</h2>", html_escape(dimension));
+            for data_type in DataType::iter() {
+                if let Some(items) = per_dimension_detail[dimension].get(&data_type) {
+                    write_section(&mut body, &mut tables, "h3", &data_type.to_string(), items);
+                }
+            }
+            if let Some(items) = report
+                .per_dimension_summary
+                .as_ref()
+                .and_then(|summary| summary.get(dimension))
+            {
+                write_section(&mut body, &mut tables, "h3", "Summary", items);
+            }
+            body.push_str("</section>\n");
+        }
+    } else if let Some(per_dimension) = &report.per_dimension_summary {
+        let mut dimensions: Vec<_> = per_dimension.keys().collect();
+        dimensions.sort();
+        for dimension in dimensions {
+            body.push_str("<section>\n");
+            let title = format!("Dimension: {dimension}");
+            write_section(
+                &mut body,
+                &mut tables,
+                "h2",
+                &title,
+                &per_dimension[dimension],
+            );
+            body.push_str("</section>\n");
+        }
+    }
+    if let Some(per_data_type) = &report.per_data_type_summary {
+        for data_type in DataType::iter() {
+            if let Some(items) = per_data_type.get(&data_type) {
+                body.push_str("<section>\n");
+                write_section(&mut body, &mut tables, "h2", &data_type.to_string(), items);
+                body.push_str("</section>\n");
+            }
+        }
+    }
+    body.push_str("<section>\n");
+    write_section(&mut body, &mut tables, "h2", "Total", &report.grand_total);
+    let _ = writeln!(
+        body,
+        "<p>Total items matched: {}</p>",
+        report.grand_total_count
+    );
+    body.push_str("</section>\n");
+
+    if !source_trees.is_empty() {
+        body.push_str("<section>\n<h2>Sources</h2>\n<ul class=\"tree\">\n");
+        for tree in source_trees {
+            write_tree_node(&mut body, tree);
+        }
+        body.push_str("</ul>\n</section>\n");
+    }
+
+    html_page("nbt-sniffer report", &world_subtitle(args), &body)
+}
+
+/// Renders trade offers as a self-contained HTML page.
+pub fn render_trades_html(trades: &[TradeOffer], args: &CliArgs) -> String {
+    let mut body = String::from("<section>\n");
+    let mut tables = 0;
+    write_section(&mut body, &mut tables, "h2", "Trade offers", trades);
+    let _ = writeln!(body, "<p>Total offers matched: {}</p>", trades.len());
+    body.push_str("</section>\n");
+    html_page("nbt-sniffer trade offers", &world_subtitle(args), &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::structures::ReportItemNbt;
+
+    #[test]
+    fn pretty_prints_snbt() {
+        assert_eq!(
+            pretty_snbt(r#"{"minecraft:custom_name":"a, {b}",ids:[I;1,2],empty:{},lore:["x"]}"#),
+            "{\n  \"minecraft:custom_name\": \"a, {b}\",\n  ids: [I;1,2],\n  empty: {},\n  lore: [\n    \"x\"\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn escapes_table_cells() {
+        let mut out = String::new();
+        write_table(
+            &mut out,
+            "t",
+            &[ReportItemNbt {
+                count: 2,
+                nbt: Some(r#"{name:"<b>"}"#.to_string()),
+            }],
+        );
+        assert!(out.contains("<th>NBT</th><th>Count</th>"));
+        assert!(out.contains("<pre>{\n  name: &quot;&lt;b&gt;&quot;\n}</pre>"));
+        assert!(out.contains("<td class=\"number\">2</td>"));
+    }
+}
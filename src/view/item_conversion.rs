@@ -1,27 +1,32 @@
-use super::structures::{ReportItemDetailed, ReportItemId, ReportItemNbt};
-use crate::{counter::Counter, escape_nbt_string};
+use super::structures::{ReportItemDetailed, ReportItemEnchantment, ReportItemId, ReportItemNbt};
+use crate::{counter::Counter, enchantments::Enchantment, escape_nbt_string};
 use std::collections::HashMap;
 
 pub fn to_detailed_item_entries(counter: &Counter) -> Vec<ReportItemDetailed> {
     let mut detailed_vec: Vec<_> = counter
         .detailed_counts()
         .iter()
-        .map(|(item_key, &count)| (item_key.id.clone(), item_key.components_snbt.clone(), count))
+        .map(|(item_key, &count)| (item_key, count))
         .collect();
 
-    detailed_vec.sort_by(|(a_id, a_nbt, a_count), (b_id, b_nbt, b_count)| {
+    detailed_vec.sort_by(|(a_key, a_count), (b_key, b_count)| {
         b_count
             .cmp(a_count)
-            .then_with(|| a_id.cmp(b_id))
-            .then_with(|| a_nbt.cmp(b_nbt))
+            .then_with(|| a_key.id.cmp(&b_key.id))
+            .then_with(|| a_key.depth.cmp(&b_key.depth))
+            .then_with(|| a_key.components.cmp(&b_key.components))
     });
 
     detailed_vec
         .iter()
-        .map(|(id, nbt_opt, count)| ReportItemDetailed {
+        .map(|(item_key, count)| ReportItemDetailed {
             count: *count,
-            id: id.clone(),
-            nbt: nbt_opt.as_ref().map(|s| escape_nbt_string(s)),
+            id: item_key.id.clone(),
+            depth: item_key.depth,
+            nbt: item_key
+                .components
+                .as_ref()
+                .map(|components| escape_nbt_string(components.snbt())),
         })
         .collect()
 }
@@ -34,6 +39,22 @@ pub fn to_id_item_entries(map: &HashMap<String, u64>) -> Vec<ReportItemId> {
         .map(|(id, count)| ReportItemId {
             count: *count,
             id: id.clone(),
+            depth: None,
+        })
+        .collect()
+}
+
+pub fn to_id_depth_item_entries(map: &HashMap<(String, Option<u32>), u64>) -> Vec<ReportItemId> {
+    let mut vec: Vec<_> = map.iter().collect();
+    vec.sort_by(|(a_key, a_count), (b_key, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_key.cmp(b_key))
+    });
+
+    vec.iter()
+        .map(|((id, depth), count)| ReportItemId {
+            count: **count,
+            id: id.clone(),
+            depth: *depth,
         })
         .collect()
 }
@@ -48,7 +69,21 @@ pub fn to_nbt_item_entries(counter: &Counter) -> Vec<ReportItemNbt> {
         .iter()
         .map(|(nbt_opt, count)| ReportItemNbt {
             count: *count,
-            nbt: nbt_opt.as_ref().map(|s| escape_nbt_string(s)),
+            nbt: nbt_opt.map(|components| escape_nbt_string(components.snbt())),
+        })
+        .collect()
+}
+
+pub fn to_enchantment_item_entries(map: &HashMap<Enchantment, u64>) -> Vec<ReportItemEnchantment> {
+    let mut vec: Vec<_> = map.iter().collect();
+    vec.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+    vec.iter()
+        .map(|(enchantment, count)| ReportItemEnchantment {
+            count: **count,
+            enchantment: enchantment.id.clone(),
+            level: enchantment.level,
+            target: enchantment.target,
         })
         .collect()
 }
@@ -0,0 +1,228 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use super::{
+    records::{TabularRecord, column_title},
+    structures::Report,
+};
+use crate::{DataType, trades::TradeOffer, tree::ItemSummaryNode};
+
+/// Escapes text for a Markdown table cell, where pipes end the cell and line breaks end
+/// the row. GitHub unescapes `\|` inside code spans too.
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// Formats SNBT as inline code, with a fence longer than any backtick run it contains.
+fn inline_code(snbt: &str) -> String {
+    let longest_run = snbt.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run + 1);
+    let padding = if snbt.starts_with('`') || snbt.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{fence}{padding}{snbt}{padding}{fence}")
+}
+
+/// Appends a table of `records`. Columns holding only numbers are right-aligned.
+fn write_table<TRecord: TabularRecord>(out: &mut String, records: &[TRecord]) {
+    let rows: Vec<Vec<String>> = records.iter().map(TabularRecord::fields).collect();
+    let numeric: Vec<bool> = (0..TRecord::COLUMNS.len())
+        .map(|column| {
+            let mut values = rows
+                .iter()
+                .map(|row| &row[column])
+                .filter(|v| !v.is_empty());
+            let mut any = false;
+            let all_numeric = values.all(|v| {
+                any = true;
+                v.parse::<f64>().is_ok()
+            });
+            any && all_numeric
+        })
+        .collect();
+
+    let titles: Vec<_> = TRecord::COLUMNS.iter().map(|c| column_title(c)).collect();
+    let _ = writeln!(out, "| {} |", titles.join(" | "));
+    let alignments: Vec<_> = numeric
+        .iter()
+        .map(|&numeric| if numeric { "---:" } else { "---" })
+        .collect();
+    let _ = writeln!(out, "| {} |", alignments.join(" | "));
+    for row in rows {
+        let cells: Vec<_> = TRecord::COLUMNS
+            .iter()
+            .zip(row)
+            .map(|(column, field)| {
+                if *column == "nbt" && !field.is_empty() {
+                    escape_cell(&inline_code(&field))
+                } else {
+                    escape_cell(&field)
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "| {} |", cells.join(" | "));
+    }
+    out.push('\n');
+}
+
+/// Appends a heading and its table, skipping empty sections.
+fn write_section<TRecord: TabularRecord>(
+    out: &mut String,
+    level: usize,
+    title: &str,
+    records: &[TRecord],
+) {
+    if records.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "{} {title}\n", "#".repeat(level));
+    write_table(out, records);
+}
+
+fn write_tree_node(out: &mut String, node: &ItemSummaryNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let (label, children) = match node {
+        ItemSummaryNode::Root { label, children } => (format!("**{label}**"), children),
+        ItemSummaryNode::Item {
+            id,
+            count,
+            snbt,
+            children,
+        } => {
+            let snbt = snbt
+                .as_deref()
+                .map(|snbt| format!(" {}", inline_code(snbt)))
+                .unwrap_or_default();
+            (format!("{count}x {id}{snbt}"), children)
+        }
+    };
+    let _ = writeln!(out, "{indent}- {label}");
+    for child in children {
+        write_tree_node(out, child, depth + 1);
+    }
+}
+
+/// Renders `report` and the per-source trees as GitHub-flavored Markdown, with sections in
+/// the same order as the table output.
+pub fn render_report_markdown<TItem: TabularRecord + Serialize>(
+    report: &Report<TItem>,
+    source_trees: &[ItemSummaryNode],
+) -> String {
+    let mut out = String::new();
+
+    if let Some(per_dimension_detail) = &report.per_dimension_detail {
+        let mut dimensions: Vec<_> = per_dimension_detail.keys().collect();
+        dimensions.sort();
+        for dimension in dimensions {
+            let _ = writeln!(out, "## Dimension: {dimension}\n");
+            for data_type in DataType::iter() {
+                if let Some(items) = per_dimension_detail[dimension].get(&data_type) {
+                    write_section(&mut out, 3, &data_type.to_string(), items);
+                }
+            }
+            if let Some(items) = report
+                .per_dimension_summary
+                .as_ref()
+                .and_then(|summary| summary.get(dimension))
+            {
+                write_section(&mut out, 3, "Summary", items);
+            }
+        }
+    } else if let Some(per_dimension) = &report.per_dimension_summary {
+        let mut dimensions: Vec<_> = per_dimension.keys().collect();
+        dimensions.sort();
+        for dimension in dimensions {
+            let title = format!("Dimension: {dimension}");
+            write_section(&mut out, 2, &title, &per_dimension[dimension]);
+        }
+    }
+    if let Some(per_data_type) = &report.per_data_type_summary {
+        for data_type in DataType::iter() {
+            if let Some(items) = per_data_type.get(&data_type) {
+                write_section(&mut out, 2, &data_type.to_string(), items);
+            }
+        }
+    }
+    write_section(&mut out, 2, "Total", &report.grand_total);
+    let _ = writeln!(out, "**Total items matched:** {}", report.grand_total_count);
+
+    if !source_trees.is_empty() {
+        out.push_str("\n## Sources\n\n");
+        for tree in source_trees {
+            write_tree_node(&mut out, tree, 0);
+        }
+    }
+    out
+}
+
+/// Renders trade offers as a Markdown table.
+pub fn render_trades_markdown(trades: &[TradeOffer]) -> String {
+    let mut out = String::new();
+    write_section(&mut out, 2, "Trade offers", trades);
+    let _ = writeln!(out, "**Total offers matched:** {}", trades.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::structures::{ReportItemDetailed, ReportItemId};
+
+    #[test]
+    fn renders_tables_with_escaped_cells() {
+        let mut out = String::new();
+        write_table(
+            &mut out,
+            &[ReportItemDetailed {
+                count: 12,
+                id: "minecraft:paper".to_string(),
+                depth: None,
+                nbt: Some(r#"{"minecraft:custom_name":"a|b"}"#.to_string()),
+            }],
+        );
+        assert_eq!(
+            out,
+            "| ID | Depth | NBT | Count |\n\
+             | --- | --- | --- | ---: |\n\
+             | minecraft:paper |  | `{\"minecraft:custom_name\":\"a\\|b\"}` | 12 |\n\n"
+        );
+    }
+
+    #[test]
+    fn renders_sections_and_trees() {
+        let report = Report {
+            per_dimension_summary: None,
+            per_data_type_summary: None,
+            per_dimension_detail: None,
+            grand_total: vec![ReportItemId {
+                count: 3,
+                id: "minecraft:dirt".to_string(),
+                depth: None,
+            }],
+            grand_total_count: 3,
+        };
+        let tree = ItemSummaryNode::new_root(
+            "[overworld] minecraft:chest @ 1 2 3".to_string(),
+            vec![ItemSummaryNode::new_item(
+                "minecraft:shulker_box".to_string(),
+                1,
+                None,
+                vec![ItemSummaryNode::new_item(
+                    "minecraft:dirt".to_string(),
+                    3,
+                    None,
+                    vec![],
+                )],
+            )],
+        );
+        let markdown = render_report_markdown(&report, &[tree]);
+        assert!(markdown.starts_with("## Total\n\n| ID | Depth | Count |\n| --- | --- | ---: |\n"));
+        assert!(markdown.ends_with(
+            "## Sources\n\n- **[overworld] minecraft:chest @ 1 2 3**\n  - 1x minecraft:shulker_box\n    - 3x minecraft:dirt\n"
+        ));
+    }
+}
@@ -1,30 +1,47 @@
 pub mod aggregation;
 pub mod builder;
+pub mod csv_printer;
+pub mod html_printer;
 pub mod item_conversion;
 pub mod json_printer;
+pub mod markdown_printer;
+pub mod records;
 pub mod structures;
 pub mod table_printer;
 
 use std::collections::HashMap;
 
 use crate::{
-    cli::{CliArgs, OutputFormat},
-    counter::{Counter, CounterMap},
+    ScanResult,
+    cli::{CliArgs, OutputFormat, ViewMode},
+    counter::Counter,
+    diff::{DiffRow, DiffSummary},
+    enchantments::Enchantment,
+    history::HistoryRow,
+    trades::TradeOffer,
 };
 use aggregation::{AggregationResult, IsEmpty};
 use serde::Serialize;
 use serde_json::json;
 
 use builder::generate_report_data;
-use item_conversion::{to_detailed_item_entries, to_id_item_entries, to_nbt_item_entries};
+use csv_printer::{print_records_as_csv, print_report_as_csv};
+use html_printer::{render_report_html, render_trades_html};
+use item_conversion::{
+    to_detailed_item_entries, to_enchantment_item_entries, to_id_depth_item_entries,
+    to_id_item_entries, to_nbt_item_entries,
+};
 use json_printer::print_json_output;
+use markdown_printer::{render_report_markdown, render_trades_markdown};
+use records::TabularRecord;
 use table_printer::{
-    print_detailed_counter, print_id_map, print_nbt_counter, print_report_as_tables,
+    print_detailed_counter, print_diff_rows, print_enchantment_map, print_history_rows,
+    print_id_map, print_nbt_counter, print_report_as_tables, print_trade_offers,
 };
 
 /// Generic helper to generate and output a report based on the view mode.
 fn generate_and_output_report<TAggregable, FConvert, FPrintTable, TReportItem>(
-    counter_map: &CounterMap,
+    result: &ScanResult,
     args: &CliArgs,
     item_converter: FConvert,
     table_printer: FPrintTable,
@@ -33,9 +50,9 @@ fn generate_and_output_report<TAggregable, FConvert, FPrintTable, TReportItem>(
     TAggregable: aggregation::Aggregable,
     FConvert: Fn(&TAggregable) -> Vec<TReportItem>,
     FPrintTable: FnMut(&[TReportItem]),
-    TReportItem: Serialize + Clone,
+    TReportItem: Serialize + Clone + TabularRecord,
 {
-    let data_provider = AggregationResult::<TAggregable>::new(counter_map);
+    let data_provider = AggregationResult::<TAggregable>::new(&result.counter_map);
     let grand_total_numeric_count = grand_total_calculator(&data_provider.total_combined);
 
     let report_data = generate_report_data(
@@ -51,14 +68,26 @@ fn generate_and_output_report<TAggregable, FConvert, FPrintTable, TReportItem>(
             json!({ "error": format!("Failed to serialize report: {e}") })
         });
         print_json_output(&json_value, args.format == OutputFormat::PrettyJson);
+    } else if let Some(delimiter) = args.format.delimiter() {
+        print_report_as_csv(&report_data, delimiter);
+    } else if args.format == OutputFormat::Html {
+        print!(
+            "{}",
+            render_report_html(&report_data, &result.source_trees, args)
+        );
+    } else if args.format == OutputFormat::Markdown {
+        print!(
+            "{}",
+            render_report_markdown(&report_data, &result.source_trees)
+        );
     } else {
         print_report_as_tables(&report_data, args, table_printer);
     }
 }
 
-pub fn view_detailed(counter_map: &CounterMap, args: &CliArgs) {
+pub fn view_detailed(result: &ScanResult, args: &CliArgs) {
     generate_and_output_report(
-        counter_map,
+        result,
         args,
         to_detailed_item_entries,
         print_detailed_counter,
@@ -66,9 +95,9 @@ pub fn view_detailed(counter_map: &CounterMap, args: &CliArgs) {
     );
 }
 
-pub fn view_by_nbt(counter_map: &CounterMap, args: &CliArgs) {
+pub fn view_by_nbt(result: &ScanResult, args: &CliArgs) {
     generate_and_output_report(
-        counter_map,
+        result,
         args,
         to_nbt_item_entries,
         print_nbt_counter,
@@ -76,9 +105,19 @@ pub fn view_by_nbt(counter_map: &CounterMap, args: &CliArgs) {
     );
 }
 
-pub fn view_by_id(counter_map: &CounterMap, args: &CliArgs) {
+pub fn view_by_id(result: &ScanResult, args: &CliArgs) {
+    if args.group_by_depth {
+        generate_and_output_report(
+            result,
+            args,
+            to_id_depth_item_entries,
+            print_id_map,
+            |map: &HashMap<(String, Option<u32>), u64>| map.values().sum(),
+        );
+        return;
+    }
     generate_and_output_report(
-        counter_map,
+        result,
         args,
         to_id_item_entries,
         print_id_map,
@@ -86,12 +125,97 @@ pub fn view_by_id(counter_map: &CounterMap, args: &CliArgs) {
     );
 }
 
+pub fn view_by_enchantment(result: &ScanResult, args: &CliArgs) {
+    generate_and_output_report(
+        result,
+        args,
+        to_enchantment_item_entries,
+        print_enchantment_map,
+        |map: &HashMap<Enchantment, u64>| map.values().sum(),
+    );
+}
+
+/// Prints trade offers grouped by profession, level and sold item.
+pub fn view_trades(trades: &mut [TradeOffer], args: &CliArgs) {
+    trades.sort_by(|a, b| {
+        a.group_key()
+            .cmp(&b.group_key())
+            .then_with(|| a.buy.cmp(&b.buy))
+            .then_with(|| a.dimension.cmp(&b.dimension))
+            .then_with(|| a.position.cmp(&b.position))
+    });
+
+    if args.format.is_json() {
+        let json_value = json!({ "trades": trades, "total_offers": trades.len() });
+        print_json_output(&json_value, args.format == OutputFormat::PrettyJson);
+    } else if args.format == OutputFormat::Ndjson {
+        for trade in trades.iter() {
+            print_json_output(&json!(trade), false);
+        }
+    } else if let Some(delimiter) = args.format.delimiter() {
+        print_records_as_csv(trades, delimiter);
+    } else if args.format == OutputFormat::Html {
+        print!("{}", render_trades_html(trades, args));
+    } else if args.format == OutputFormat::Markdown {
+        print!("{}", render_trades_markdown(trades));
+    } else {
+        print_trade_offers(trades);
+    }
+}
+
+/// Prints the items compared by `diff`, in the table, JSON or delimited formats.
+pub fn view_diff(rows: &[DiffRow], summary: &DiffSummary, args: &CliArgs) {
+    let Some(diff) = args.diff_args() else {
+        return;
+    };
+    if args.format.is_json() {
+        let json_value = json!({ "items": rows, "summary": summary });
+        print_json_output(&json_value, args.format == OutputFormat::PrettyJson);
+    } else if let Some(delimiter) = args.format.delimiter() {
+        print_records_as_csv(rows, delimiter);
+    } else {
+        print_diff_rows(rows, diff.by_location, args.view == ViewMode::Detailed);
+        println!(
+            "\nTotal: {} -> {} ({:+}), gained {}, lost {}",
+            summary.old_total,
+            summary.new_total,
+            summary.new_total as i64 - summary.old_total as i64,
+            summary.gained,
+            summary.lost
+        );
+        let hidden_unchanged = if diff.show_unchanged {
+            0
+        } else {
+            summary.unchanged_rows
+        };
+        if hidden_unchanged > 0 || summary.hidden_rows > 0 {
+            println!(
+                "Not shown: {hidden_unchanged} unchanged, {} below the change thresholds",
+                summary.hidden_rows
+            );
+        }
+    }
+}
+
+/// Prints the item counts of every backup found by `history`.
+pub fn view_history(rows: &[HistoryRow], backups: usize, args: &CliArgs) {
+    if args.format.is_json() {
+        let json_value = json!({ "rows": rows, "backups": backups });
+        print_json_output(&json_value, args.format == OutputFormat::PrettyJson);
+    } else if let Some(delimiter) = args.format.delimiter() {
+        print_records_as_csv(rows, delimiter);
+    } else {
+        print_history_rows(rows, args.view == ViewMode::Detailed);
+        println!("\nBackups scanned: {backups}");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
         DataType, Scope,
-        cli::{CliArgs, OutputFormat, ViewMode},
+        cli::{CliArgs, HeatmapResolution, OutputFormat, ViewMode},
         counter::{Counter, CounterMap},
         view::aggregation::SummaryDataProvider,
     };
@@ -178,16 +302,42 @@ mod tests {
 
     fn mock_cli_args() -> CliArgs {
         CliArgs {
-            world_path: PathBuf::from("dummy"),
+            world_path: Some(PathBuf::from("dummy")),
             all: true,
             items: vec![],
+            filters: vec![],
+            excludes: vec![],
+            name: None,
+            lore_contains: vec![],
+            enchantments: vec![],
+            min_depth: None,
+            max_depth: None,
+            inside: vec![],
+            in_containers: vec![],
+            nested_item_paths: vec![],
+            no_default_nested_paths: false,
+            lenient_numbers: false,
+            modified_since: None,
+            modified_before: None,
+            min_inhabited_ticks: None,
+            vanilla_data: None,
             view: ViewMode::ById,
+            trades: false,
+            group_by_depth: false,
             show_nbt: false,
             per_source_summary: false,
             per_dimension_summary: false,
             per_data_type_summary: false,
             verbose: false,
             format: OutputFormat::Table,
+            export_sqlite: None,
+            heatmap: None,
+            heatmap_resolution: HeatmapResolution::Chunk,
+            save_snapshot: None,
+            snapshot_locations: false,
+            from_snapshot: None,
+            cache_dir: None,
+            command: None,
         }
     }
 
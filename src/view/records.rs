@@ -0,0 +1,150 @@
+use super::structures::{ReportItemDetailed, ReportItemEnchantment, ReportItemId, ReportItemNbt};
+use crate::{diff::DiffRow, history::HistoryRow, trades::TradeOffer};
+
+/// A report item or other record shown as one row of a CSV, TSV or HTML table.
+pub trait TabularRecord {
+    /// Column names, in the order of `fields`.
+    const COLUMNS: &'static [&'static str];
+
+    fn fields(&self) -> Vec<String>;
+}
+
+/// Human-readable title of a column, e.g. `max_uses` becomes `Max uses`.
+pub fn column_title(column: &str) -> String {
+    match column {
+        "id" => "ID".to_string(),
+        "nbt" => "NBT".to_string(),
+        _ => {
+            let mut title = column.replace('_', " ");
+            if let Some(first) = title.get_mut(..1) {
+                first.make_ascii_uppercase();
+            }
+            title
+        }
+    }
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+impl TabularRecord for ReportItemDetailed {
+    const COLUMNS: &'static [&'static str] = &["id", "depth", "nbt", "count"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            optional(&self.depth),
+            optional(&self.nbt),
+            self.count.to_string(),
+        ]
+    }
+}
+
+impl TabularRecord for ReportItemId {
+    const COLUMNS: &'static [&'static str] = &["id", "depth", "count"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            optional(&self.depth),
+            self.count.to_string(),
+        ]
+    }
+}
+
+impl TabularRecord for ReportItemNbt {
+    const COLUMNS: &'static [&'static str] = &["nbt", "count"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![optional(&self.nbt), self.count.to_string()]
+    }
+}
+
+impl TabularRecord for ReportItemEnchantment {
+    const COLUMNS: &'static [&'static str] = &["enchantment", "level", "target", "count"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.enchantment.clone(),
+            self.level.to_string(),
+            self.target.label().to_lowercase(),
+            self.count.to_string(),
+        ]
+    }
+}
+
+impl TabularRecord for TradeOffer {
+    const COLUMNS: &'static [&'static str] = &[
+        "profession",
+        "level",
+        "sell",
+        "price",
+        "uses",
+        "max_uses",
+        "trader",
+        "dimension",
+        "position",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.profession.clone(),
+            optional(&self.level),
+            self.sell.label(),
+            self.price_label(),
+            self.uses.to_string(),
+            self.max_uses.to_string(),
+            self.trader.clone(),
+            self.dimension.clone(),
+            self.position.clone(),
+        ]
+    }
+}
+
+impl TabularRecord for DiffRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "dimension",
+        "data_type",
+        "holder",
+        "position",
+        "player",
+        "id",
+        "nbt",
+        "old_count",
+        "new_count",
+        "change",
+        "status",
+    ];
+
+    fn fields(&self) -> Vec<String> {
+        let location = self.location.as_ref();
+        vec![
+            self.dimension.clone(),
+            format!("{:?}", self.data_type),
+            location.map(|l| l.holder.clone()).unwrap_or_default(),
+            location.map(|l| l.position.clone()).unwrap_or_default(),
+            location.and_then(|l| l.player.clone()).unwrap_or_default(),
+            self.id.clone(),
+            optional(&self.nbt),
+            self.old_count.to_string(),
+            self.new_count.to_string(),
+            self.change.to_string(),
+            self.status.to_string(),
+        ]
+    }
+}
+
+impl TabularRecord for HistoryRow {
+    const COLUMNS: &'static [&'static str] = &["backup", "timestamp", "id", "nbt", "count"];
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.backup.clone(),
+            self.timestamp.clone(),
+            self.id.clone(),
+            optional(&self.nbt),
+            self.count.to_string(),
+        ]
+    }
+}
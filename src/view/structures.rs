@@ -1,13 +1,15 @@
 use serde::Serialize;
 use std::collections::HashMap;
 
-use crate::DataType;
+use crate::{DataType, enchantments::EnchantmentTarget};
 
 #[derive(Serialize, Clone)]
 pub struct ReportItemDetailed {
     pub count: u64,
     pub id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub nbt: Option<String>,
 }
 
@@ -15,6 +17,8 @@ pub struct ReportItemDetailed {
 pub struct ReportItemId {
     pub count: u64,
     pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
 }
 
 #[derive(Serialize, Clone)]
@@ -24,6 +28,14 @@ pub struct ReportItemNbt {
     pub nbt: Option<String>,
 }
 
+#[derive(Serialize, Clone)]
+pub struct ReportItemEnchantment {
+    pub count: u64,
+    pub enchantment: String,
+    pub level: i64,
+    pub target: EnchantmentTarget,
+}
+
 #[derive(Serialize)]
 pub struct Report<TItem: Serialize> {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -1,7 +1,12 @@
-use super::structures::{Report, ReportItemDetailed, ReportItemId, ReportItemNbt};
+use super::structures::{
+    Report, ReportItemDetailed, ReportItemEnchantment, ReportItemId, ReportItemNbt,
+};
 use crate::{
     DataType,
     cli::{CliArgs, ViewMode},
+    diff::DiffRow,
+    history::HistoryRow,
+    trades::TradeOffer,
 };
 use comfy_table::{Cell, CellAlignment, ContentArrangement, Table, presets};
 use serde::Serialize;
@@ -186,32 +191,157 @@ pub fn print_detailed_counter(items: &[ReportItemDetailed]) {
     if items.is_empty() {
         return;
     }
+    let nbt_cell =
+        |item: &ReportItemDetailed| Cell::new(item.nbt.clone().unwrap_or_else(|| "No NBT".into()));
+    if items.iter().any(|item| item.depth.is_some()) {
+        print_table(
+            &["Count", "ID", "Depth", "NBT"],
+            items,
+            |item| {
+                vec![
+                    Cell::new(item.count),
+                    Cell::new(&item.id),
+                    depth_cell(item.depth),
+                    nbt_cell(item),
+                ]
+            },
+            Some(3),
+        );
+    } else {
+        print_table(
+            &["Count", "ID", "NBT"],
+            items,
+            |item| vec![Cell::new(item.count), Cell::new(&item.id), nbt_cell(item)],
+            Some(2),
+        );
+    }
+}
+
+pub fn print_id_map(items: &[ReportItemId]) {
+    if items.is_empty() {
+        return;
+    }
+    if items.iter().any(|item| item.depth.is_some()) {
+        print_table(
+            &["Count", "Item ID", "Depth"],
+            items,
+            |item| {
+                vec![
+                    Cell::new(item.count),
+                    Cell::new(&item.id),
+                    depth_cell(item.depth),
+                ]
+            },
+            None,
+        );
+    } else {
+        print_table(
+            &["Count", "Item ID"],
+            items,
+            |item| vec![Cell::new(item.count), Cell::new(&item.id)],
+            None,
+        );
+    }
+}
+
+pub fn print_trade_offers(offers: &[TradeOffer]) {
+    if offers.is_empty() {
+        return;
+    }
     print_table(
-        &["Count", "ID", "NBT"],
-        items,
-        |item| {
+        &["Profession", "Level", "Sells", "Price", "Uses", "Trader"],
+        offers,
+        |offer| {
             vec![
-                Cell::new(item.count),
-                Cell::new(&item.id),
-                Cell::new(item.nbt.clone().unwrap_or_else(|| "No NBT".into())),
+                Cell::new(&offer.profession),
+                Cell::new(
+                    offer
+                        .level
+                        .map_or_else(|| "-".to_string(), |l| l.to_string()),
+                ),
+                Cell::new(offer.sell.label()),
+                Cell::new(offer.price_label()),
+                Cell::new(format!("{}/{}", offer.uses, offer.max_uses)),
+                Cell::new(format!("{} {}", offer.dimension, offer.position)),
             ]
         },
-        Some(2),
+        None,
     );
 }
 
-pub fn print_id_map(items: &[ReportItemId]) {
-    if items.is_empty() {
+/// Prints compared items, with holder and NBT columns only when they were compared.
+pub fn print_diff_rows(rows: &[DiffRow], by_location: bool, with_nbt: bool) {
+    if rows.is_empty() {
         return;
     }
+    let mut headers = vec!["Dimension", "Data Type"];
+    if by_location {
+        headers.push("Holder");
+    }
+    headers.push("ID");
+    if with_nbt {
+        headers.push("NBT");
+    }
+    headers.extend(["Old", "New", "Change"]);
     print_table(
-        &["Count", "Item ID"],
-        items,
-        |item| vec![Cell::new(item.count), Cell::new(&item.id)],
+        &headers,
+        rows,
+        |row| {
+            let mut cells = vec![Cell::new(&row.dimension), Cell::new(row.data_type)];
+            if let Some(location) = &row.location {
+                let holder = match &location.player {
+                    Some(player) => format!("{} {player}", location.holder),
+                    None => format!("{} @ {}", location.holder, location.position),
+                };
+                cells.push(Cell::new(holder));
+            }
+            cells.push(Cell::new(&row.id));
+            if with_nbt {
+                cells.push(Cell::new(row.nbt.as_deref().unwrap_or("No NBT")));
+            }
+            cells.extend([
+                Cell::new(row.old_count),
+                Cell::new(row.new_count),
+                Cell::new(format!("{:+}", row.change)),
+            ]);
+            cells
+        },
+        None,
+    );
+}
+
+pub fn print_history_rows(rows: &[HistoryRow], with_nbt: bool) {
+    if rows.is_empty() {
+        return;
+    }
+    let mut headers = vec!["Backup", "Timestamp", "ID"];
+    if with_nbt {
+        headers.push("NBT");
+    }
+    headers.push("Count");
+    print_table(
+        &headers,
+        rows,
+        |row| {
+            let mut cells = vec![
+                Cell::new(&row.backup),
+                Cell::new(&row.timestamp),
+                Cell::new(&row.id),
+            ];
+            if with_nbt {
+                cells.push(Cell::new(row.nbt.as_deref().unwrap_or("No NBT")));
+            }
+            cells.push(Cell::new(row.count));
+            cells
+        },
         None,
     );
 }
 
+fn depth_cell(depth: Option<u32>) -> Cell {
+    Cell::new(depth.map_or_else(|| "-".to_string(), |d| d.to_string()))
+}
+
 pub fn print_nbt_counter(items: &[ReportItemNbt]) {
     if items.is_empty() {
         return;
@@ -229,6 +359,25 @@ pub fn print_nbt_counter(items: &[ReportItemNbt]) {
     );
 }
 
+pub fn print_enchantment_map(items: &[ReportItemEnchantment]) {
+    if items.is_empty() {
+        return;
+    }
+    print_table(
+        &["Count", "Enchantment", "Level", "On"],
+        items,
+        |item| {
+            vec![
+                Cell::new(item.count),
+                Cell::new(&item.enchantment),
+                Cell::new(item.level),
+                Cell::new(item.target.label()),
+            ]
+        },
+        None,
+    );
+}
+
 fn print_table<T, F>(
     headers: &[&str],
     data: &[T],